@@ -0,0 +1,337 @@
+use anyhow::Result;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    http::{header, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::{AuthUser, Role, SERVER_CONF};
+
+pub const SESSION_COOKIE: &str = "zapm_session";
+// Web 界面发送修改请求时需要带上会话的 CSRF 令牌
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(12 * 3600);
+
+// 通过认证的请求者，保存在请求的扩展中
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub name: String,
+    pub role: Role,
+    // 通过会话 cookie 认证时为会话的 CSRF 令牌，通过 API 令牌认证时为 None
+    pub csrf_token: Option<String>,
+}
+
+impl Identity {
+    // 事件日志中记录的操作者
+    pub fn actor(&self) -> String {
+        match self.csrf_token {
+            Some(_) => format!("web:{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// 会话只保存用户名，角色在每次请求时从配置中读取，重新加载配置后立即生效
+struct Session {
+    name: String,
+    csrf_token: String,
+    expires: Instant,
+}
+
+static SESSIONS: Lazy<Mutex<HashMap<String, Session>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Operator 以下的角色看到的环境变量值，变量值中常有密钥
+pub const MASKED_VALUE: &str = "******";
+
+// 配置了用户时启用认证
+pub fn enabled() -> bool {
+    SERVER_CONF
+        .read()
+        .unwrap()
+        .auth
+        .as_ref()
+        .map(|auth| !auth.users.is_empty())
+        .unwrap_or(false)
+}
+
+fn users() -> Vec<AuthUser> {
+    SERVER_CONF.read().unwrap().auth.as_ref().map(|auth| auth.users.clone()).unwrap_or_default()
+}
+
+pub fn session_ttl() -> Result<Duration> {
+    let ttl = SERVER_CONF.read().unwrap().auth.as_ref().and_then(|auth| auth.session_ttl.clone());
+    match ttl {
+        Some(ttl) => humantime::parse_duration(&ttl).map_err(|_| anyhow::anyhow!("Invalid session_ttl '{}'", ttl)),
+        None => Ok(DEFAULT_SESSION_TTL),
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// 比较令牌时不因第一个不同的字节提前返回
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// 生成配置文件中使用的 argon2 密码哈希
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))
+}
+
+// 配置中的密码哈希是否可以解析
+pub fn is_valid_hash(hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok()
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+fn find_by_token(token: &str) -> Option<AuthUser> {
+    users().into_iter().find(|user| {
+        user.token
+            .as_deref()
+            .map(|expected| constant_time_eq(expected.as_bytes(), token.as_bytes()))
+            .unwrap_or(false)
+    })
+}
+
+// 校验用户名和密码，或者 API 令牌
+pub fn authenticate(name: Option<&str>, password: Option<&str>, token: Option<&str>) -> Option<AuthUser> {
+    if let Some(token) = token.filter(|token| !token.is_empty()) {
+        return find_by_token(token);
+    }
+    let (name, password) = (name?, password?);
+    users()
+        .into_iter()
+        .find(|user| user.name == name)
+        .filter(|user| user.password_hash.as_deref().map(|hash| verify_password(hash, password)).unwrap_or(false))
+}
+
+// 创建登录会话，返回会话 ID 和会话的身份
+pub fn create_session(user: &AuthUser, ttl: Duration) -> (String, Identity) {
+    let id = random_token();
+    let identity = Identity {
+        name: user.name.clone(),
+        role: user.role,
+        csrf_token: Some(random_token()),
+    };
+    let mut sessions = SESSIONS.lock().unwrap();
+    let now = Instant::now();
+    sessions.retain(|_, session| session.expires > now);
+    sessions.insert(
+        id.clone(),
+        Session {
+            name: user.name.clone(),
+            csrf_token: identity.csrf_token.clone().unwrap_or_default(),
+            expires: now + ttl,
+        },
+    );
+    (id, identity)
+}
+
+pub fn remove_session(id: &str) {
+    SESSIONS.lock().unwrap().remove(id);
+}
+
+// 会话过期或用户已从配置中删除时删除会话
+fn session(id: &str) -> Option<Identity> {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions.get(id)?;
+    let user = users().into_iter().find(|user| user.name == session.name);
+    match user {
+        Some(user) if session.expires > Instant::now() => Some(Identity {
+            name: user.name,
+            role: user.role,
+            csrf_token: Some(session.csrf_token.clone()),
+        }),
+        _ => {
+            sessions.remove(id);
+            None
+        }
+    }
+}
+
+pub fn session_cookie(id: &str, ttl: Duration) -> String {
+    format!("{}={}; HttpOnly; SameSite=Strict; Path=/; Max-Age={}", SESSION_COOKIE, id, ttl.as_secs())
+}
+
+pub fn clear_cookie() -> String {
+    format!("{}=; HttpOnly; SameSite=Strict; Path=/; Max-Age=0", SESSION_COOKIE)
+}
+
+// 请求中的会话 ID
+pub fn session_id<B>(request: &Request<B>) -> Option<String> {
+    request
+        .headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+}
+
+// 依次尝试 Authorization: Bearer 和会话 cookie
+fn identify<B>(request: &Request<B>) -> Option<Identity> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return find_by_token(token.trim()).map(|user| Identity {
+            name: user.name,
+            role: user.role,
+            csrf_token: None,
+        });
+    }
+    session_id(request).and_then(|id| session(&id))
+}
+
+// 请求者能否查看环境变量的值，未启用认证时不限制
+pub fn can_view_env(identity: Option<&Identity>) -> bool {
+    !enabled() || identity.map(|identity| identity.role >= Role::Operator).unwrap_or(false)
+}
+
+// 把 JSON 中所有进程配置的 env 值替换为 MASKED_VALUE，保留变量名
+pub fn mask_env(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                match child {
+                    serde_json::Value::Object(env) if key == "env" => {
+                        for value in env.values_mut() {
+                            *value = serde_json::Value::String(MASKED_VALUE.to_string());
+                        }
+                    }
+                    child => mask_env(child),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(mask_env),
+        _ => {}
+    }
+}
+
+// 不需要登录的路径：登录页面和静态文件
+fn is_public(path: &str) -> bool {
+    path == "/login" || path == "/api/login" || path.starts_with("/static/")
+}
+
+// 执行请求需要的角色，hub 转发给 agent 的请求按 agent 上的路径判断
+fn required_role(method: &Method, path: &str) -> Role {
+    if method == Method::GET || method == Method::HEAD || path == "/api/logout" {
+        return Role::Viewer;
+    }
+    if let Some((_, rest)) = path.strip_prefix("/api/hosts/").and_then(|rest| rest.split_once('/')) {
+        return required_role(method, &format!("/api/{}", rest.trim_start_matches('/')));
+    }
+    let action = path.strip_prefix("/api/processes/").and_then(|rest| rest.split_once('/')).map(|(_, action)| action);
+    match action {
+        Some("start" | "stop" | "restart" | "reload" | "scale") => Role::Operator,
+        _ => Role::Admin,
+    }
+}
+
+// 启动请求中的 command、working_dir、env 和 auto_restart 会保存到进程配置中，与修改配置一样需要 admin
+pub fn start_role(overrides: bool) -> Role {
+    if overrides {
+        Role::Admin
+    } else {
+        required_role(&Method::POST, "/api/processes/_/start")
+    }
+}
+
+pub fn forbidden_message(identity: &Identity, required: Role) -> String {
+    format!("User {} ({}) requires the {} role for this action", identity.name, identity.role.as_str(), required.as_str())
+}
+
+// 认证请求并检查角色，通过会话 cookie 认证的修改请求还需要 CSRF 令牌
+pub async fn middleware<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    let path = request.uri().path().to_string();
+    if !enabled() || is_public(&path) {
+        return next.run(request).await;
+    }
+
+    let identity = match identify(&request) {
+        Some(identity) => identity,
+        None if path.starts_with("/api/") || path == "/metrics" => {
+            return (StatusCode::UNAUTHORIZED, "Authentication required").into_response()
+        }
+        None => return Redirect::to("/login").into_response(),
+    };
+
+    let method = request.method().clone();
+    if method != Method::GET && method != Method::HEAD {
+        if let Some(expected) = &identity.csrf_token {
+            let valid = request
+                .headers()
+                .get(CSRF_HEADER)
+                .map(|value| constant_time_eq(value.as_bytes(), expected.as_bytes()))
+                .unwrap_or(false);
+            if !valid {
+                return (StatusCode::FORBIDDEN, "Missing or invalid CSRF token").into_response();
+            }
+        }
+    }
+
+    let required = required_role(&method, &path);
+    if identity.role < required {
+        return (StatusCode::FORBIDDEN, forbidden_message(&identity, required)).into_response();
+    }
+
+    request.extensions_mut().insert(identity);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_roles_by_action() {
+        let cases = [
+            (Method::GET, "/api/processes", Role::Viewer),
+            (Method::GET, "/api/processes/api/logs", Role::Viewer),
+            (Method::POST, "/api/logout", Role::Viewer),
+            (Method::POST, "/api/processes/api/start", Role::Operator),
+            (Method::POST, "/api/processes/api/stop", Role::Operator),
+            (Method::POST, "/api/processes/api/scale", Role::Operator),
+            (Method::POST, "/api/processes/api", Role::Admin),
+            (Method::DELETE, "/api/processes/api", Role::Admin),
+            (Method::POST, "/api/config/reload", Role::Admin),
+            (Method::POST, "/api/hosts/web-1/processes/api/restart", Role::Operator),
+            (Method::POST, "/api/hosts/web-1/processes/api", Role::Admin),
+        ];
+        for (method, path, expected) in cases {
+            assert_eq!(required_role(&method, path), expected, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn requires_admin_to_override_start_settings() {
+        assert_eq!(start_role(false), Role::Operator);
+        assert_eq!(start_role(true), Role::Admin);
+    }
+}
@@ -0,0 +1,395 @@
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{ProcessConfig, SERVER_CONF};
+use crate::utils;
+
+// 守护进程自身所在的叶子节点，cgroup v2 不允许非根节点同时包含进程和子节点
+const DAEMON_LEAF: &str = "daemon";
+// 每个托管进程对应的子节点前缀
+const PROCESS_PREFIX: &str = "process.";
+
+// zapm 管理的 cgroup 目录，None 表示 cgroup v2 不可用或未委派
+static BASE: Lazy<Option<PathBuf>> = Lazy::new(|| match setup() {
+    Ok(base) => base,
+    Err(e) => {
+        eprintln!("Warning: cgroup v2 integration disabled: {:#}", e);
+        None
+    }
+});
+
+// cgroup 中的资源使用情况
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub memory_current: Option<u64>,
+    pub memory_peak: Option<u64>,
+    pub memory_max: Option<String>,
+    pub cpu_usage_usec: Option<u64>,
+    pub pids_current: Option<u64>,
+    pub oom: Option<u64>,
+    pub oom_kill: Option<u64>,
+}
+
+// 查找 cgroup2 的挂载点
+fn mount_point() -> Option<PathBuf> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mountinfo.lines().find_map(|line| {
+        let (left, right) = line.split_once(" - ")?;
+        if right.split_whitespace().next()? != "cgroup2" {
+            return None;
+        }
+        left.split_whitespace().nth(4).map(PathBuf::from)
+    })
+}
+
+// 读取指定进程所在的 cgroup v2 路径
+fn cgroup_of(mount: &Path, pid: &str) -> Option<PathBuf> {
+    let content = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| mount.join(path.trim_start_matches('/')))
+}
+
+// 判断 cgroup 是否已委派给当前进程：非根节点、可写，且不是登录会话的 scope
+fn is_delegated(mount: &Path, base: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    if base == mount || base.extension().map(|e| e == "scope").unwrap_or(false) {
+        return false;
+    }
+    [base.to_path_buf(), base.join("cgroup.subtree_control"), base.join("cgroup.procs")]
+        .iter()
+        .all(|path| match CString::new(path.as_os_str().as_bytes()) {
+            Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+            Err(_) => false,
+        })
+}
+
+// 初始化 zapm 的 cgroup 子树
+fn setup() -> Result<Option<PathBuf>> {
+    let enabled = SERVER_CONF.read().unwrap().cgroup;
+    if enabled == Some(false) {
+        return Ok(None);
+    }
+
+    let mount = match mount_point() {
+        Some(mount) => mount,
+        None if enabled == Some(true) => return Err(anyhow::anyhow!("cgroup2 filesystem is not mounted")),
+        None => return Ok(None),
+    };
+    let mut base = cgroup_of(&mount, "self")
+        .ok_or_else(|| anyhow::anyhow!("Failed to determine the cgroup of the zapm daemon"))?;
+
+    // 守护进程重启后可能仍然位于上次创建的叶子节点中
+    if base.file_name().map(|n| n == DAEMON_LEAF).unwrap_or(false) && base != mount {
+        if let Some(parent) = base.parent() {
+            base = parent.to_path_buf();
+        }
+    }
+
+    if enabled.is_none() && !is_delegated(&mount, &base) {
+        return Ok(None);
+    }
+
+    // 将当前节点中的进程（包括守护进程自己）移动到叶子节点
+    let procs = fs::read_to_string(base.join("cgroup.procs")).unwrap_or_default();
+    if base != mount && !procs.trim().is_empty() {
+        let leaf = base.join(DAEMON_LEAF);
+        fs::create_dir_all(&leaf).with_context(|| format!("Failed to create {}", leaf.display()))?;
+        for pid in procs.lines() {
+            // 进程可能已经退出
+            let _ = fs::write(leaf.join("cgroup.procs"), pid);
+        }
+    }
+
+    // 为子节点启用 memory、cpu、pids 控制器
+    let available = fs::read_to_string(base.join("cgroup.controllers")).unwrap_or_default();
+    let controllers: Vec<String> = ["memory", "cpu", "pids"]
+        .iter()
+        .filter(|c| available.split_whitespace().any(|a| a == **c))
+        .map(|c| format!("+{}", c))
+        .collect();
+    if !controllers.is_empty() {
+        fs::write(base.join("cgroup.subtree_control"), controllers.join(" "))
+            .with_context(|| format!("Failed to enable cgroup controllers in {}", base.display()))?;
+    }
+
+    Ok(Some(base))
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+// 进程对应的 cgroup 目录
+pub fn path_for(name: &str) -> Option<PathBuf> {
+    BASE.as_ref().map(|base| base.join(format!("{}{}", PROCESS_PREFIX, sanitize(name))))
+}
+
+// 查找运行中进程所属的 zapm cgroup，CLI 与守护进程都可以使用
+pub fn of_pid(pid: u32) -> Option<PathBuf> {
+    let mount = mount_point()?;
+    let path = cgroup_of(&mount, &pid.to_string())?;
+    let is_managed = path
+        .file_name()
+        .map(|n| n.to_string_lossy().starts_with(PROCESS_PREFIX))
+        .unwrap_or(false);
+    if is_managed {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn has_limits(config: &ProcessConfig) -> bool {
+    config.memory_max.is_some() || config.cpu_weight.is_some() || config.cpu_max.is_some() || config.pids_max.is_some()
+}
+
+// 写入一项限制；未配置时恢复默认值，避免沿用上一次的设置
+fn write_limit(dir: &Path, file: &str, value: Option<String>, default: &str) -> Result<()> {
+    let path = dir.join(file);
+    match value {
+        Some(value) => {
+            if !path.exists() {
+                return Err(anyhow::anyhow!(
+                    "Cannot set {}: the controller is not enabled in {}",
+                    file,
+                    dir.display()
+                ));
+            }
+            fs::write(&path, &value).with_context(|| format!("Failed to write {} to {}", value, path.display()))
+        }
+        None => {
+            if path.exists() {
+                let _ = fs::write(&path, default);
+            }
+            Ok(())
+        }
+    }
+}
+
+// cpu.max 中配额的下限和周期的范围（微秒），超出时内核拒绝写入
+const MIN_CPU_QUOTA: u64 = 1000;
+const CPU_PERIOD_RANGE: std::ops::RangeInclusive<u64> = 1000..=1_000_000;
+
+// 将 "50%"、"max"、"50000 100000" 或 "max 100000" 转换为 cpu.max 的格式
+fn parse_cpu_max(value: &str) -> Result<String> {
+    let value = value.trim();
+    let invalid = || anyhow::anyhow!("Invalid cpu_max '{}', expected a percentage like 50% or \"quota period\"", value);
+    if value == "max" {
+        return Ok("max 100000".to_string());
+    }
+    let (quota, period) = match value.strip_suffix('%') {
+        Some(percent) => {
+            let percent: f64 = percent.trim().parse().map_err(|_| invalid())?;
+            if !percent.is_finite() || percent <= 0.0 {
+                return Err(invalid());
+            }
+            (Some((percent * 1000.0).round() as u64), 100000)
+        }
+        None => {
+            let mut parts = value.split_whitespace();
+            let quota = match parts.next() {
+                Some("max") => None,
+                Some(quota) => Some(quota.parse::<u64>().map_err(|_| invalid())?),
+                None => return Err(invalid()),
+            };
+            let period = match parts.next() {
+                Some(period) => period.parse::<u64>().map_err(|_| invalid())?,
+                None => 100000,
+            };
+            if parts.next().is_some() {
+                return Err(invalid());
+            }
+            (quota, period)
+        }
+    };
+    if !CPU_PERIOD_RANGE.contains(&period) {
+        return Err(anyhow::anyhow!("Invalid cpu_max '{}', the period must be between 1000 and 1000000", value));
+    }
+    match quota {
+        Some(quota) if quota < MIN_CPU_QUOTA => Err(anyhow::anyhow!(
+            "Invalid cpu_max '{}', the quota must be at least {} microseconds",
+            value,
+            MIN_CPU_QUOTA
+        )),
+        Some(quota) => Ok(format!("{} {}", quota, period)),
+        None => Ok(format!("max {}", period)),
+    }
+}
+
+fn parse_memory_max(value: &str) -> Result<String> {
+    if value.trim() == "max" {
+        return Ok("max".to_string());
+    }
+    Ok(utils::parse_memory_size(value)?.to_string())
+}
+
+// 校验 cgroup 限制的格式，不检查 cgroup v2 是否可用
+pub fn validate(config: &ProcessConfig) -> Result<()> {
+    config.memory_max.as_deref().map(parse_memory_max).transpose()?;
+    config.cpu_max.as_deref().map(parse_cpu_max).transpose()?;
+    if let Some(weight) = config.cpu_weight {
+        if !(1..=10000).contains(&weight) {
+            return Err(anyhow::anyhow!("cpu_weight must be between 1 and 10000"));
+        }
+    }
+    Ok(())
+}
+
+// 启动前创建 cgroup 并写入限制，返回打开的 cgroup.procs，在 pre_exec 中用于加入该 cgroup
+pub fn prepare(config: &ProcessConfig) -> Result<Option<fs::File>> {
+    let dir = match path_for(&config.name) {
+        Some(dir) => dir,
+        None if has_limits(config) => {
+            return Err(anyhow::anyhow!(
+                "Process {} sets cgroup limits but cgroup v2 is not available or not delegated to zapm",
+                config.name
+            ))
+        }
+        None => return Ok(None),
+    };
+
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create cgroup {}", dir.display()))?;
+
+    validate(config)?;
+    let memory_max = config.memory_max.as_deref().map(parse_memory_max).transpose()?;
+    let cpu_max = config.cpu_max.as_deref().map(parse_cpu_max).transpose()?;
+
+    let result = write_limit(&dir, "memory.max", memory_max, "max")
+        .and_then(|_| write_limit(&dir, "cpu.weight", config.cpu_weight.map(|w| w.to_string()), "100"))
+        .and_then(|_| write_limit(&dir, "cpu.max", cpu_max, "max 100000"))
+        .and_then(|_| write_limit(&dir, "pids.max", config.pids_max.map(|p| p.to_string()), "max"))
+        .and_then(|_| {
+            fs::OpenOptions::new()
+                .write(true)
+                .open(dir.join("cgroup.procs"))
+                .with_context(|| format!("Failed to open {}", dir.join("cgroup.procs").display()))
+        });
+    if result.is_err() {
+        let _ = fs::remove_dir(&dir);
+    }
+    result.map(Some)
+}
+
+// 在 fork 之后、exec 之前把子进程加入 cgroup，"0" 表示写入者自身
+pub fn join(procs: &fs::File) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let written = unsafe { libc::write(procs.as_raw_fd(), b"0".as_ptr() as *const libc::c_void, 1) };
+    if written < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// 终止 cgroup 中的所有进程
+pub fn kill(dir: &Path) -> Result<()> {
+    match fs::write(dir.join("cgroup.kill"), "1") {
+        Ok(_) => Ok(()),
+        // 内核 5.14 之前没有 cgroup.kill，逐个终止
+        Err(e) if e.kind() == ErrorKind::NotFound || e.kind() == ErrorKind::PermissionDenied => {
+            let procs = fs::read_to_string(dir.join("cgroup.procs"))?;
+            for pid in procs.lines().filter_map(|p| p.trim().parse::<libc::pid_t>().ok()) {
+                unsafe {
+                    libc::kill(pid, libc::SIGKILL);
+                }
+            }
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// 等待 cgroup 中的进程全部退出后删除目录
+pub fn remove(dir: &Path) {
+    for _ in 0..20 {
+        let empty = fs::read_to_string(dir.join("cgroup.procs"))
+            .map(|procs| procs.trim().is_empty())
+            .unwrap_or(true);
+        if empty {
+            let _ = fs::remove_dir(dir);
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// 读取 cgroup 中的资源使用情况
+pub fn stats(dir: &Path) -> Stats {
+    let read = |file: &str| fs::read_to_string(dir.join(file)).ok();
+    let read_u64 = |file: &str| read(file).and_then(|v| v.trim().parse::<u64>().ok());
+    let read_key = |file: &str, key: &str| {
+        read(file).and_then(|content| {
+            content.lines().find_map(|line| {
+                let (k, v) = line.split_once(' ')?;
+                if k == key {
+                    v.trim().parse::<u64>().ok()
+                } else {
+                    None
+                }
+            })
+        })
+    };
+
+    Stats {
+        memory_current: read_u64("memory.current"),
+        memory_peak: read_u64("memory.peak"),
+        memory_max: read("memory.max").map(|v| v.trim().to_string()),
+        cpu_usage_usec: read_key("cpu.stat", "usage_usec"),
+        pids_current: read_u64("pids.current"),
+        oom: read_key("memory.events", "oom"),
+        oom_kill: read_key("memory.events", "oom_kill"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cpu_max() {
+        let cases = [
+            ("max", "max 100000"),
+            (" max ", "max 100000"),
+            ("max 100000", "max 100000"),
+            ("max 50000", "max 50000"),
+            ("50%", "50000 100000"),
+            ("150%", "150000 100000"),
+            ("1%", "1000 100000"),
+            ("12.5 %", "12500 100000"),
+            ("50000", "50000 100000"),
+            ("50000 100000", "50000 100000"),
+            ("200000 1000000", "200000 1000000"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_cpu_max(input).unwrap(), expected, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_cpu_max() {
+        for input in [
+            "", "%", "0%", "-5%", "nan%", "inf%", "0.5%", "abc", "50 %x", "999", "50000 999",
+            "50000 2000000", "50000 100000 1", "-1", "max max", "18446744073709551616",
+        ] {
+            assert!(parse_cpu_max(input).is_err(), "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_memory_max() {
+        assert_eq!(parse_memory_max("max").unwrap(), "max");
+        assert_eq!(parse_memory_max("512M").unwrap(), (512 * 1024 * 1024).to_string());
+        assert!(parse_memory_max("lots").is_err());
+    }
+}
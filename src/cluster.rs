@@ -0,0 +1,370 @@
+use anyhow::Result;
+use chrono::Local;
+
+use crate::config::{self, get_process, update_process, InstancesValue, ProcessConfig, ProcessStatus, PROCESSES};
+use crate::events;
+use crate::process;
+use crate::schedule;
+#[cfg(unix)]
+use crate::sockets;
+
+// 集群模式：设置了 instances 的进程只作为模板，实际运行的是 <name>-0 ... <name>-(N-1) 这些实例，
+// 实例是普通的进程配置，通过 instance_of 指向所属的进程
+
+// 是否为集群模式的进程（模板），而不是其中的实例
+pub fn is_parent(config: &ProcessConfig) -> bool {
+    config.instances.is_some() && config.instance_of.is_none()
+}
+
+pub fn instance_name(parent: &str, id: u32) -> String {
+    format!("{}-{}", parent, id)
+}
+
+// 解析命令行中的实例数
+pub fn parse_instances(value: &str) -> Result<InstancesValue> {
+    let instances = match value.trim().parse::<u32>() {
+        Ok(n) => InstancesValue::Number(n),
+        Err(_) => InstancesValue::Text(value.trim().to_string()),
+    };
+    resolve_count(&instances)?;
+    Ok(instances)
+}
+
+// 计算实际的实例数，"max" 为 CPU 核数
+pub fn resolve_count(instances: &InstancesValue) -> Result<u32> {
+    match instances {
+        InstancesValue::Number(n) => Ok(*n),
+        InstancesValue::Text(s) if s == "max" => Ok(std::thread::available_parallelism()?.get() as u32),
+        InstancesValue::Text(s) => s
+            .parse::<u32>()
+            .map_err(|_| anyhow::anyhow!("Invalid instances '{}', expected a number or \"max\"", s)),
+    }
+}
+
+// 集群的所有实例，按实例编号排序
+pub fn instances_of(parent: &str) -> Vec<ProcessConfig> {
+    let processes = PROCESSES.read().unwrap();
+    let mut instances: Vec<ProcessConfig> = processes
+        .values()
+        .filter(|config| config.instance_of.as_deref() == Some(parent))
+        .cloned()
+        .collect();
+    instances.sort_by_key(|config| config.instance_id);
+    instances
+}
+
+// 根据实例的状态计算集群的整体状态，普通进程原样返回
+pub fn with_summary(mut config: ProcessConfig) -> ProcessConfig {
+    if !is_parent(&config) {
+        return config;
+    }
+    let instances = instances_of(&config.name);
+    let running = instances.iter().filter(|i| i.status == ProcessStatus::Running).count() as u32;
+    config.running_instances = Some(running);
+    config.pid = None;
+    config.status = if running > 0 {
+        ProcessStatus::Running
+    } else if instances.iter().any(|i| i.status == ProcessStatus::Failed) {
+        ProcessStatus::Failed
+    } else {
+        ProcessStatus::Stopped
+    };
+    config
+}
+
+// 按模板生成实例的配置，保留已有实例的运行状态和统计
+fn materialize(parent: &ProcessConfig, id: u32, existing: Option<ProcessConfig>) -> ProcessConfig {
+    let now = Local::now().to_rfc3339();
+    let mut instance = parent.clone();
+    instance.name = instance_name(&parent.name, id);
+    instance.instances = None;
+    instance.instance_of = Some(parent.name.clone());
+    instance.instance_id = Some(id);
+    instance.running_instances = None;
+    instance.updated_at = now.clone();
+
+    match existing {
+        Some(existing) => {
+            instance.start_time = existing.start_time;
+            instance.status = existing.status;
+            instance.pid = existing.pid;
+            instance.created_at = existing.created_at;
+            instance.restarts = existing.restarts;
+            instance.crashes = existing.crashes;
+            instance.history = existing.history;
+        }
+        None => {
+            instance.start_time = None;
+            instance.status = ProcessStatus::Stopped;
+            instance.pid = None;
+            instance.created_at = now;
+            instance.restarts = 0;
+            instance.crashes = 0;
+            instance.history = Vec::new();
+        }
+    }
+    instance
+}
+
+// 实例依次使用 port、port+1 ...，最后一个实例的端口不能超过 65535
+fn check_port_range(parent: &ProcessConfig, count: u32) -> Result<()> {
+    if let Some(port) = parent.port {
+        if count > 0 && port as u32 + count - 1 > u16::MAX as u32 {
+            return Err(anyhow::anyhow!("Port range {}-{} of process {} is out of range", port, port as u32 + count - 1, parent.name));
+        }
+    }
+    Ok(())
+}
+
+// 使实例与 instances 配置一致：创建缺少的实例、更新已有实例的配置、停止并删除多余的实例
+// 返回新增和删除的实例名
+pub fn sync(name: &str) -> Result<(Vec<String>, Vec<String>)> {
+    let parent = get_process(name).ok_or_else(|| anyhow::anyhow!("Process {} not found", name))?;
+    let count = match &parent.instances {
+        Some(instances) if parent.instance_of.is_none() => resolve_count(instances)?,
+        _ => return Err(anyhow::anyhow!("Process {} is not in cluster mode", name)),
+    };
+    check_port_range(&parent, count)?;
+
+    let mut added = Vec::new();
+    for id in 0..count {
+        let instance_name = instance_name(name, id);
+        let existing = get_process(&instance_name);
+        if let Some(existing) = &existing {
+            if existing.instance_of.as_deref() != Some(name) {
+                return Err(anyhow::anyhow!(
+                    "Cannot create instance {}: a process with that name already exists",
+                    instance_name
+                ));
+            }
+        } else {
+            added.push(instance_name);
+        }
+        update_process(materialize(&parent, id, existing))?;
+    }
+
+    let mut removed = Vec::new();
+    for instance in instances_of(name) {
+        if instance.instance_id.map(|id| id >= count).unwrap_or(true) {
+            process::remove_process(&instance.name, false)?;
+            removed.push(instance.name);
+        }
+    }
+    Ok((added, removed))
+}
+
+fn set_status(name: &str, status: ProcessStatus) -> Result<()> {
+    if let Some(mut config) = get_process(name) {
+        config.status = status;
+        config.pid = None;
+        config.updated_at = Local::now().to_rfc3339();
+        update_process(config)?;
+    }
+    Ok(())
+}
+
+// 对每个实例执行操作，一个实例失败不影响其他实例
+fn for_each_instance<F>(name: &str, action: &str, mut f: F) -> Result<()>
+where
+    F: FnMut(&ProcessConfig) -> Result<()>,
+{
+    let errors: Vec<String> = instances_of(name)
+        .iter()
+        .filter_map(|instance| f(instance).err().map(|e| format!("{}: {:#}", instance.name, e)))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Failed to {} instances of {}: {}", action, name, errors.join("; ")))
+    }
+}
+
+// 启动集群的所有实例
+pub fn start(name: &str) -> Result<()> {
+    sync(name)?;
+    set_status(name, ProcessStatus::Running)?;
+    for_each_instance(name, "start", |instance| {
+        process::start_process(&instance.name, &instance.command, instance.working_dir.as_deref(), None)
+    })
+}
+
+// 停止集群的所有实例
+pub fn stop(name: &str) -> Result<()> {
+    let result = for_each_instance(name, "stop", |instance| process::stop_process(&instance.name));
+    set_status(name, ProcessStatus::Stopped)?;
+    result
+}
+
+// 依次重启集群的所有实例
+pub fn restart(name: &str, reason: &str) -> Result<()> {
+    sync(name)?;
+    set_status(name, ProcessStatus::Running)?;
+    let result = for_each_instance(name, "restart", |instance| {
+        process::restart_process_with_reason(&instance.name, reason)
+    });
+    if let Some(mut config) = get_process(name) {
+        config.restarts += 1;
+        config.record_history("restart", Some(reason.to_string()));
+        update_process(config)?;
+    }
+    result
+}
+
+// 滚动重启：依次替换每个实例，先启动新进程并等待就绪，再停止旧进程；
+// 某个实例的新进程启动失败或未能就绪时中止，该实例和尚未替换的实例继续运行原来的进程
+pub async fn reload(name: &str) -> Result<()> {
+    let config = get_process(name).ok_or_else(|| anyhow::anyhow!("Process {} not found", name))?;
+    if !is_parent(&config) {
+        return process::replace_process(name, "reload").await;
+    }
+
+    let parent = name.to_string();
+    events::spawn_blocking(move || -> Result<()> {
+        sync(&parent)?;
+        set_status(&parent, ProcessStatus::Running)
+    })
+    .await??;
+    let instances = instances_of(name);
+    for (index, instance) in instances.iter().enumerate() {
+        if let Err(e) = process::replace_process(&instance.name, "reload").await {
+            return Err(e.context(format!(
+                "Reload of {} aborted at {} after {}/{} instances; the remaining instances are still running",
+                name,
+                instance.name,
+                index,
+                instances.len()
+            )));
+        }
+    }
+
+    if let Some(mut config) = get_process(name) {
+        config.record_history("reload", None);
+        update_process(config)?;
+    }
+    Ok(())
+}
+
+// 删除集群及其所有实例
+pub fn remove(name: &str, force: bool) -> Result<()> {
+    for_each_instance(name, "remove", |instance| process::remove_process(&instance.name, force))?;
+    config::remove_process(name)?;
+    #[cfg(unix)]
+    sockets::close(name);
+    Ok(())
+}
+
+// 调整实例数量，只启动新增的实例或停止多余的实例，不影响其他实例
+pub fn scale(name: &str, instances: InstancesValue) -> Result<()> {
+    let mut config = get_process(name).ok_or_else(|| anyhow::anyhow!("Process {} not found", name))?;
+    if let Some(parent) = &config.instance_of {
+        return Err(anyhow::anyhow!("Process {} is an instance of {}, scale {} instead", name, parent, parent));
+    }
+    if !is_parent(&config) && config.status == ProcessStatus::Running {
+        return Err(anyhow::anyhow!("Stop process {} before switching it to cluster mode", name));
+    }
+    let count = resolve_count(&instances)?;
+
+    let running = config.status == ProcessStatus::Running
+        || instances_of(name).iter().any(|i| i.status == ProcessStatus::Running);
+    config.instances = Some(instances);
+    schedule::validate(&config)?;
+    config.updated_at = Local::now().to_rfc3339();
+    config.record_history("scale", Some(format!("{} instances", count)));
+    update_process(config)?;
+
+    let (added, removed) = sync(name)?;
+    for instance in &removed {
+        println!("Instance {} removed", instance);
+    }
+    if !running {
+        return Ok(());
+    }
+    let errors: Vec<String> = added
+        .iter()
+        .filter_map(|instance| {
+            let config = get_process(instance)?;
+            process::start_process(instance, &config.command, config.working_dir.as_deref(), None)
+                .err()
+                .map(|e| format!("{}: {:#}", instance, e))
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Failed to start instances of {}: {}", name, errors.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment;
+
+    fn parent() -> ProcessConfig {
+        ProcessConfig {
+            name: "web".to_string(),
+            command: "./web".to_string(),
+            instances: Some(InstancesValue::Number(3)),
+            port: Some(8000),
+            inherit_env: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_instance_counts() {
+        assert_eq!(resolve_count(&parse_instances(" 4 ").unwrap()).unwrap(), 4);
+        assert!(resolve_count(&parse_instances("max").unwrap()).unwrap() >= 1);
+        assert!(parse_instances("many").is_err());
+        assert!(parse_instances("-1").is_err());
+    }
+
+    #[test]
+    fn materializes_named_instances() {
+        let parent = parent();
+        let instance = materialize(&parent, 2, None);
+        assert_eq!(instance.name, "web-2");
+        assert_eq!(instance.instance_of.as_deref(), Some("web"));
+        assert_eq!(instance.instance_id, Some(2));
+        assert!(instance.instances.is_none());
+        assert!(is_parent(&parent));
+        assert!(!is_parent(&instance));
+        assert_eq!((instance.status, instance.pid, instance.restarts), (ProcessStatus::Stopped, None, 0));
+        assert_eq!(instance.command, "./web");
+    }
+
+    #[test]
+    fn keeps_runtime_state_of_existing_instances() {
+        let mut existing = materialize(&parent(), 1, None);
+        existing.status = ProcessStatus::Running;
+        existing.pid = Some(42);
+        existing.restarts = 5;
+        let mut updated = parent();
+        updated.command = "./web --v2".to_string();
+        let instance = materialize(&updated, 1, Some(existing));
+        assert_eq!((instance.status, instance.pid, instance.restarts), (ProcessStatus::Running, Some(42), 5));
+        assert_eq!(instance.command, "./web --v2");
+    }
+
+    #[test]
+    fn assigns_a_port_per_instance() {
+        let parent = parent();
+        for id in 0..3 {
+            let env = environment::build_env(&materialize(&parent, id, None)).unwrap();
+            assert_eq!(env["PORT"], (8000 + id).to_string());
+            assert_eq!(env["ZAPM_INSTANCE_ID"], id.to_string());
+        }
+    }
+
+    #[test]
+    fn checks_the_port_range() {
+        let mut parent = parent();
+        parent.port = Some(65533);
+        assert!(check_port_range(&parent, 3).is_ok());
+        let error = check_port_range(&parent, 4).unwrap_err();
+        assert_eq!(error.to_string(), "Port range 65533-65536 of process web is out of range");
+        assert!(check_port_range(&parent, 0).is_ok());
+        parent.port = None;
+        assert!(check_port_range(&parent, 100000).is_ok());
+    }
+}
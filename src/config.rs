@@ -1,9 +1,12 @@
 use once_cell::sync::Lazy;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
-use std::sync::RwLock;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::SystemTime;
 
 // 配置文件路径
 pub static CONFIG_PATH: Lazy<PathBuf> = Lazy::new(|| {
@@ -32,53 +35,664 @@ pub static PROCESS_CONFIG_PATH: Lazy<PathBuf> = Lazy::new(|| {
     path
 });
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ServerConf {
     pub host : String,
     pub port: u16,
-    #[serde(skip_serializing)]
+    #[serde(skip_serializing, default)]
+    #[schemars(skip)]
     pub api_base_url: String,
-    
+    // 是否为进程创建 cgroup v2 子树，未设置时在 cgroup 已委派给 zapm 时自动启用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<bool>,
+    // 资源采样间隔（默认 10s）、保留时长（默认 6h），以及是否保存到 metrics.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_interval: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_retention: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_persist: Option<bool>,
+    // 接收进程事件的 webhook
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConf>,
+    // 事件日志的保留时长（默认 30d）和最多保留的条数（默认 10000）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events_retention: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events_max: Option<usize>,
+    // 进程日志文件超过该大小（默认 10M）时转存为 <日志>.1 并清空，0 表示不限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_max_size: Option<String>,
+    // Web API 和界面的认证，未配置用户时不启用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthConf>,
+    // 集中管理模式，汇总并代理其他 zapm 守护进程
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hub: Option<HubConf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct HubConf {
+    // 也可以通过 zapm server --hub 启用
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub hosts: Vec<HostConf>,
+}
+
+// 由 hub 管理的 zapm 守护进程（agent）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct HostConf {
+    pub name: String,
+    pub url: String,
+    // agent 启用认证时使用的 API 令牌
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct AuthConf {
+    #[serde(default)]
+    pub users: Vec<AuthUser>,
+    // Web 界面登录会话的有效期（默认 12h）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_ttl: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct AuthUser {
+    pub name: String,
+    #[serde(default)]
+    pub role: Role,
+    // argon2 密码哈希，由 zapm hash-password 生成，用于 Web 界面登录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    // API 令牌，通过 Authorization: Bearer 请求头使用，也可以用于 Web 界面登录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+// 用户角色：viewer 只能查看，operator 还可以启动、停止、重启和扩缩容，admin 还可以添加、修改和删除进程
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Viewer,
+    Operator,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+// 以 JSON POST 进程事件的 webhook
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct WebhookConf {
+    pub url: String,
+    // 设置后在 X-Zapm-Signature 头中附带请求体的 HMAC-SHA256 签名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    // 只发送这些事件（started、stopped、exited、restarted、healthy、unhealthy、errored、updated、removed），默认全部发送
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<String>>,
+    // 发送失败后的重试次数（默认 3），重试间隔从 1s 开始加倍
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+impl Default for ServerConf {
+    fn default() -> Self {
+        ServerConf {
+            host: "localhost".to_string(),
+            port: 2400,
+            api_base_url: "http://localhost:2400".to_string(),
+            cgroup: None,
+            metrics_interval: None,
+            metrics_retention: None,
+            metrics_persist: None,
+            webhooks: Vec::new(),
+            events_retention: None,
+            events_max: None,
+            log_max_size: None,
+            auth: None,
+            hub: None,
+        }
+    }
+}
+
+// zapm server 的命令行参数，重新加载 zapm.yaml 后仍然优先于配置文件
+#[derive(Debug, Default)]
+pub struct ServerOverrides {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub hub: bool,
+}
+
+impl ServerOverrides {
+    pub fn apply(&self, conf: &mut ServerConf) {
+        if let Some(host) = &self.host {
+            conf.host = host.clone();
+        }
+        if let Some(port) = self.port {
+            conf.port = port;
+        }
+        if self.hub {
+            conf.hub.get_or_insert_with(Default::default).enabled = true;
+        }
+    }
 }
 
+pub static SERVER_OVERRIDES: Lazy<RwLock<ServerOverrides>> = Lazy::new(|| RwLock::new(ServerOverrides::default()));
+
 // 进程配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessConfig {
     pub start_time: Option<std::time::SystemTime>,
     pub name: String,
     pub command: String,
     pub working_dir: Option<String>,
     pub env: Option<HashMap<String, String>>,
+    // dotenv 格式的环境变量文件，按顺序加载，env 中的同名变量优先
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<Vec<String>>,
+    // 是否继承守护进程自身的环境变量，默认继承
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inherit_env: Option<bool>,
+    // 不继承环境变量时，仍然从守护进程传递的变量名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_allowlist: Option<Vec<String>>,
+    // 运行进程的用户、组和附加组（仅 Unix），可以是名称或数字 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<String>>,
+    // 资源限制（仅 Linux），如 nofile: 65536、core: 0、as: "unlimited"、nproc: "512:1024"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rlimits: Option<HashMap<String, RlimitValue>>,
+    // cgroup v2 资源限制（仅 Linux），如 memory_max: 512M、cpu_max: "50%"、cpu_weight: 100、pids_max: 64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_max: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_weight: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_max: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pids_max: Option<u64>,
+    // 内存超过该值（如 512M）时平滑重启
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<String>,
+    // CPU 使用率持续 max_cpu_duration（默认 60s）超过该值时平滑重启
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu_percent: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu_duration: Option<String>,
+    // 标准输出和标准错误写入的日志文件，默认为配置目录下的 logs/<name>.log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<String>,
+    // 集群模式的实例数，可以是数字或 "max"（CPU 核数），实例名为 <name>-0、<name>-1 ...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instances: Option<InstancesValue>,
+    // 注入 PORT 环境变量，集群模式下每个实例依次加 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    // 集群实例所属的进程和实例编号，由 zapm 维护
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_of: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<u32>,
+    // 集群中正在运行的实例数，仅在查询时计算
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running_instances: Option<u32>,
+    // 就绪检查，reload 时等待新进程通过检查后再重启下一个实例
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheck>,
+    // 启动和停止前后执行的钩子命令
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+    // 由 zapm 持有的监听套接字（tcp://host:port 或 unix:///path），通过 LISTEN_FDS 传给进程，重启时保持打开
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen: Option<Vec<String>>,
+    // 进程类型：service 常驻运行，oneshot 运行一次，cron 按 schedule 定时运行
+    #[serde(rename = "type", default, skip_serializing_if = "ProcessType::is_service")]
+    pub process_type: ProcessType,
+    // cron 表达式，支持可选的秒字段，如 "0 */5 * * * *"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+    // 上一次运行尚未结束时是否允许启动新的运行（默认跳过）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_overlap: Option<bool>,
+    // 按 cron 表达式定时平滑重启常驻服务，如 "0 3 * * *"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron_restart: Option<String>,
+    // 文件变化时自动重启：true 监视 working_dir，或指定要监视的路径列表
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch: Option<WatchValue>,
+    // 监视时忽略的文件，glob 相对于监视的目录，如 "*.log"、"tmp/**"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<Vec<String>>,
+    // 最后一次文件变化后等待多久再重启（默认 1s），合并连续的变化
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_delay: Option<String>,
+    // 停止时发送 SIGTERM 后等待的时间（默认 5s），超时后强制终止
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kill_timeout: Option<String>,
     pub auto_restart: bool,
+    // 以下为运行状态，由 zapm 保存在 state.json 中，processes.yaml 中不需要填写
+    #[serde(default)]
     pub status: ProcessStatus,
     pub pid: Option<u32>,
+    #[serde(default)]
     pub created_at: String,
+    #[serde(default)]
     pub updated_at: String,
+    // 重启次数
+    #[serde(default)]
+    pub restarts: u32,
+    // 意外退出的次数
+    #[serde(default)]
+    pub crashes: u32,
+    // 最近的重启等事件及原因
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<HistoryEntry>,
+    // oneshot 和 cron 最近几次运行的结果
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<RunRecord>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq,Copy)]
+// 进程历史记录保留的条数
+const HISTORY_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct HistoryEntry {
+    pub time: String,
+    pub event: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+// 保留的运行记录条数
+const RUNS_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct RunRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    pub finished_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub status: String,
+    pub success: bool,
+}
+
+impl ProcessConfig {
+    // 指标和列表中使用的分组名，集群实例为所属的进程名，普通进程为自身名称
+    pub fn group_name(&self) -> &str {
+        self.instance_of.as_deref().unwrap_or(&self.name)
+    }
+
+    // 记录一条历史，超出上限时丢弃最早的记录
+    pub fn record_history(&mut self, event: &str, reason: Option<String>) {
+        self.history.push(HistoryEntry {
+            time: chrono::Local::now().to_rfc3339(),
+            event: event.to_string(),
+            reason,
+        });
+        if self.history.len() > HISTORY_LIMIT {
+            let excess = self.history.len() - HISTORY_LIMIT;
+            self.history.drain(..excess);
+        }
+    }
+
+    // 记录一次运行的结果，退出码为 0 即为成功
+    pub fn record_run(&mut self, started: Option<SystemTime>, status: &str, exit_code: Option<i32>) {
+        let finished = SystemTime::now();
+        self.runs.push(RunRecord {
+            started_at: started.map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339()),
+            finished_at: chrono::DateTime::<chrono::Local>::from(finished).to_rfc3339(),
+            duration_seconds: started
+                .and_then(|t| finished.duration_since(t).ok())
+                .map(|d| (d.as_secs_f64() * 1000.0).round() / 1000.0),
+            exit_code,
+            status: status.to_string(),
+            success: exit_code == Some(0),
+        });
+        if self.runs.len() > RUNS_LIMIT {
+            let excess = self.runs.len() - RUNS_LIMIT;
+            self.runs.drain(..excess);
+        }
+    }
+
+    // oneshot 和 cron 类型的进程会正常退出
+    pub fn is_job(&self) -> bool {
+        self.process_type != ProcessType::Service
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum RlimitValue {
+    Number(u64),
+    Text(String),
+}
+
+// 就绪检查，http/tcp/command 中可以使用进程的环境变量，如 http://127.0.0.1:${PORT}/health
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct HealthCheck {
+    // GET 请求返回 2xx 即为就绪
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+    // 能够建立 TCP 连接即为就绪，如 127.0.0.1:8000
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp: Option<String>,
+    // 命令退出码为 0 即为就绪
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    // 等待就绪的最长时间（默认 30s）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    // 检查间隔（默认 1s）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+}
+
+// 生命周期钩子，使用进程的环境变量、工作目录和运行身份执行，输出写入进程日志
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_stop: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_stop: Option<String>,
+    // 每个钩子的超时时间（默认 60s），超时后终止钩子并视为失败
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    // pre_start 失败时是否中止启动（默认 true），其他钩子失败只记录日志
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abort_on_failure: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum WatchValue {
+    Enabled(bool),
+    Paths(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum InstancesValue {
+    Number(u32),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessType {
+    /// 常驻服务
+    #[default]
+    Service,
+    /// 运行一次
+    Oneshot,
+    /// 按 schedule 定时运行
+    Cron,
+}
+
+impl ProcessType {
+    pub fn is_service(&self) -> bool {
+        *self == ProcessType::Service
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq,Copy, JsonSchema)]
 pub enum ProcessStatus {
     Running,
     Stopped,
     Failed,
+    // oneshot 和 cron 成功退出
+    Completed,
+    #[default]
     Unknown,
 }
 
+// 无法解析的配置文件及其错误，这些文件不会被覆盖，守护进程也不会启动
+static PARSE_ERRORS: Lazy<Mutex<HashMap<PathBuf, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn record_parse_error(path: &Path, error: &serde_yaml::Error) {
+    eprintln!("Warning: Failed to parse {}: {}", path.display(), error);
+    PARSE_ERRORS.lock().unwrap().insert(path.to_path_buf(), error.to_string());
+}
+
+// 加载时无法解析的配置文件
+pub fn parse_errors() -> Vec<String> {
+    let errors = PARSE_ERRORS.lock().unwrap();
+    let mut errors: Vec<String> = errors
+        .iter()
+        .map(|(path, error)| format!("Failed to parse {}: {}", path.display(), error))
+        .collect();
+    errors.sort();
+    errors
+}
+
+fn refuse_overwrite(path: &Path, error: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Refusing to overwrite {}, it could not be parsed ({}); fix it and check with zapm config validate",
+        path.display(),
+        error
+    )
+}
+
+// 最近一次从 processes.yaml 加载或写入的内容，以及其中的配置
+#[derive(Default)]
+struct Synced {
+    content: String,
+    settings: serde_yaml::Value,
+}
+
+static SYNCED: Lazy<Mutex<Synced>> = Lazy::new(|| Mutex::new(Synced::default()));
+
+thread_local! {
+    // 为 true 时只保存运行状态，不写入 processes.yaml
+    static STATE_ONLY: Cell<bool> = const { Cell::new(false) };
+}
+
+// 结束后恢复原来的设置，阻塞线程池中的线程会被复用
+struct RestoreStateOnly(bool);
+
+impl Drop for RestoreStateOnly {
+    fn drop(&mut self) {
+        STATE_ONLY.with(|flag| flag.set(self.0));
+    }
+}
+
+// 执行期间只保存运行状态，用于重新加载时删除文件中已经不存在的进程
+pub fn state_only<T>(f: impl FnOnce() -> T) -> T {
+    let _restore = RestoreStateOnly(STATE_ONLY.with(|flag| flag.replace(true)));
+    f()
+}
+
+// 写入前检查文件是否无法解析，或者在加载之后被手动修改过
+fn check_writable(synced: &Synced) -> anyhow::Result<()> {
+    let path = PROCESS_CONFIG_PATH.as_path();
+    if let Some(error) = PARSE_ERRORS.lock().unwrap().get(path) {
+        return Err(refuse_overwrite(path, error));
+    }
+    if fs::read_to_string(path).unwrap_or_default() != synced.content {
+        return Err(anyhow::anyhow!(
+            "Refusing to overwrite {}, it was changed on disk after zapm loaded it; apply the changes with zapm config reload first",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+// 修改进程配置前检查，避免覆盖无法解析或手动修改过的 processes.yaml
+pub fn ensure_writable() -> anyhow::Result<()> {
+    if STATE_ONLY.with(Cell::get) {
+        return Ok(());
+    }
+    Lazy::force(&PROCESSES);
+    check_writable(&SYNCED.lock().unwrap())
+}
+
+// 以 content 作为 processes.yaml 的最新内容，重新加载后调用，之后的修改以它为基准
+pub fn mark_synced(content: String) -> anyhow::Result<()> {
+    let settings = settings(&PROCESSES.read().unwrap())?;
+    *SYNCED.lock().unwrap() = Synced { content, settings };
+    Ok(())
+}
+
+// processes.yaml 中保存的配置，不包括运行状态，按进程名排序
+fn settings(processes: &HashMap<String, ProcessConfig>) -> anyhow::Result<serde_yaml::Value> {
+    let mut settings = BTreeMap::new();
+    for (name, config) in processes {
+        let mut value = serde_yaml::to_value(config)?;
+        if let serde_yaml::Value::Mapping(fields) = &mut value {
+            for field in RUNTIME_FIELDS {
+                fields.shift_remove(*field);
+            }
+        }
+        settings.insert(name.clone(), value);
+    }
+    Ok(serde_yaml::to_value(settings)?)
+}
+
+// 进程的运行状态，保存在 state.json 中，守护进程更新状态时不会改写 processes.yaml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ProcessState {
+    start_time: Option<SystemTime>,
+    status: ProcessStatus,
+    pid: Option<u32>,
+    created_at: String,
+    updated_at: String,
+    restarts: u32,
+    crashes: u32,
+    history: Vec<HistoryEntry>,
+    runs: Vec<RunRecord>,
+}
+
+impl ProcessState {
+    fn of(config: &ProcessConfig) -> Self {
+        ProcessState {
+            start_time: config.start_time,
+            status: config.status,
+            pid: config.pid,
+            created_at: config.created_at.clone(),
+            updated_at: config.updated_at.clone(),
+            restarts: config.restarts,
+            crashes: config.crashes,
+            history: config.history.clone(),
+            runs: config.runs.clone(),
+        }
+    }
+
+    fn apply(self, config: &mut ProcessConfig) {
+        config.start_time = self.start_time;
+        config.status = self.status;
+        config.pid = self.pid;
+        config.created_at = self.created_at;
+        config.updated_at = self.updated_at;
+        config.restarts = self.restarts;
+        config.crashes = self.crashes;
+        config.history = self.history;
+        config.runs = self.runs;
+    }
+}
+
+fn state_path() -> PathBuf {
+    CONFIG_PATH.join("state.json")
+}
+
+// 读取保存的运行状态，没有状态的进程（如旧版本写入的配置）沿用 processes.yaml 中的值
+fn load_state(processes: &mut HashMap<String, ProcessConfig>) {
+    let path = state_path();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return,
+    };
+    let states: HashMap<String, ProcessState> = match serde_json::from_str(&content) {
+        Ok(states) => states,
+        Err(e) => {
+            eprintln!("Warning: Failed to parse {}: {}", path.display(), e);
+            return;
+        }
+    };
+    for (name, state) in states {
+        if let Some(config) = processes.get_mut(&name) {
+            state.apply(config);
+        }
+    }
+}
+
+// 写入临时文件后替换，避免写入中途退出留下不完整的文件
+fn save_state(processes: &HashMap<String, ProcessConfig>) -> anyhow::Result<()> {
+    let states: BTreeMap<&String, ProcessState> = processes
+        .iter()
+        .map(|(name, config)| (name, ProcessState::of(config)))
+        .collect();
+    let path = state_path();
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(&states)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+// 运行状态字段，比较配置变化时忽略
+pub const RUNTIME_FIELDS: &[&str] = &[
+    "start_time",
+    "running_instances",
+    "status",
+    "pid",
+    "created_at",
+    "updated_at",
+    "restarts",
+    "crashes",
+    "history",
+    "runs",
+];
+
+// 两份进程配置之间变化的字段，不包括运行状态
+pub fn changed_fields(old: &ProcessConfig, new: &ProcessConfig) -> Vec<String> {
+    let (old, new) = match (serde_json::to_value(old), serde_json::to_value(new)) {
+        (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) => (old, new),
+        _ => return Vec::new(),
+    };
+    let mut changed: Vec<String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|key| !RUNTIME_FIELDS.contains(&key.as_str()) && old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    changed.sort_unstable();
+    changed.dedup();
+    changed
+}
+
 pub static SERVER_CONF : Lazy<RwLock<ServerConf>> = Lazy::new(|| {
     let path = CONFIG_PATH.as_path().join("zapm.yaml");
     let mut server_conf = match fs::read_to_string(&path) {
         Ok(content) => {
-            serde_yaml::from_str::<ServerConf>(&content).unwrap_or_else(|_| {
-                let server_conf = ServerConf { host: "localhost".to_string() ,port: 2400 , api_base_url: "http://localhost:2400".to_string()};
-                let zapm_yaml_rs = serde_yaml::to_string(&server_conf);
-                if let Ok(yaml_str) = zapm_yaml_rs {
-                    let _ = fs::write(path, yaml_str);
-                }
-                server_conf    
+            serde_yaml::from_str::<ServerConf>(&content).unwrap_or_else(|e| {
+                record_parse_error(&path, &e);
+                ServerConf::default()
             })
         }
         Err(_) => {
-            let server_conf = ServerConf { host: "localhost".to_string() ,port: 2400 , api_base_url: "http://localhost:2400".to_string()};
+            let server_conf = ServerConf::default();
             let zapm_yaml_rs = serde_yaml::to_string(&server_conf);
             if let Ok(yaml_str) = zapm_yaml_rs {
                 let _ = fs::write(path, yaml_str);
@@ -94,18 +708,24 @@ pub static PROCESSES: Lazy<RwLock<HashMap<String, ProcessConfig>>> = Lazy::new(|
     let path = PROCESS_CONFIG_PATH.as_path();
     
     // 带错误处理的初始化
-    let processes = match fs::read_to_string(path) {
+    let (content, mut processes) = match fs::read_to_string(path) {
+        Ok(content) if content.trim().is_empty() => (content, HashMap::new()),
         Ok(content) => {
-            serde_yaml::from_str(&content).unwrap_or_else(|_| {
-                eprintln!("Warning: Failed to parse process config, using empty map");
+            let processes = serde_yaml::from_str(&content).unwrap_or_else(|e| {
+                record_parse_error(path, &e);
                 HashMap::new()
-            })
+            });
+            (content, processes)
         }
         Err(e) => {
             eprintln!("Warning: Failed to read process config ({}), using empty map", e);
-            HashMap::new()
+            (String::new(), HashMap::new())
         }
     };
+    load_state(&mut processes);
+    if let Ok(settings) = settings(&processes) {
+        *SYNCED.lock().unwrap() = Synced { content, settings };
+    }
     
     RwLock::new(processes)
 });
@@ -140,22 +760,64 @@ fn load_processes() -> anyhow::Result<()> {
     Ok(())
 }
 
-// 保存进程配置
+// 保存进程配置：运行状态写入 state.json，processes.yaml 只在配置变化时写入
 pub fn save_processes() -> anyhow::Result<()> {
     let processes = PROCESSES.read().unwrap();
-    let content = serde_yaml::to_string(&*processes)?;
-    fs::write(PROCESS_CONFIG_PATH.as_path(), content)?;
+    save_state(&processes)?;
+    if STATE_ONLY.with(Cell::get) {
+        return Ok(());
+    }
+    let settings = settings(&processes)?;
+    let mut synced = SYNCED.lock().unwrap();
+    if synced.settings == settings {
+        return Ok(());
+    }
+    check_writable(&synced)?;
+    let content = serde_yaml::to_string(&settings)?;
+    fs::write(PROCESS_CONFIG_PATH.as_path(), &content)?;
+    *synced = Synced { content, settings };
     Ok(())
 }
 
 pub fn update_processes(update_list : Vec<ProcessConfig>) -> anyhow::Result<()> {
     let mut processes = PROCESSES.write().unwrap();
+    // 与 update_process 相同，只更新运行状态时不需要写入 processes.yaml
+    let changes_settings = update_list.iter().any(|config| {
+        processes
+            .get(&config.name)
+            .map(|old| !changed_fields(old, config).is_empty())
+            .unwrap_or(true)
+    });
+    if changes_settings {
+        ensure_writable()?;
+    }
     for config in update_list {
         processes.insert(config.name.clone(), config);
     }
-    let content = serde_yaml::to_string(&*processes)?;
-    fs::write(PROCESS_CONFIG_PATH.as_path(), content)?;
-    Ok(())
+    drop(processes);
+    save_processes()
+}
+
+// processes.yaml 的 JSON Schema
+pub fn processes_schema() -> serde_json::Value {
+    let mut schema = schemars::schema_for!(HashMap<String, ProcessConfig>);
+    schema.schema.metadata().title = Some("zapm processes.yaml".to_string());
+    serde_json::to_value(schema).unwrap_or_default()
+}
+
+// zapm.yaml 的 JSON Schema
+pub fn server_schema() -> serde_json::Value {
+    let mut schema = schemars::schema_for!(ServerConf);
+    schema.schema.metadata().title = Some("zapm zapm.yaml".to_string());
+    serde_json::to_value(schema).unwrap_or_default()
+}
+
+// 进程日志文件路径
+pub fn log_path(config: &ProcessConfig) -> PathBuf {
+    match &config.log_file {
+        Some(file) => PathBuf::from(file),
+        None => CONFIG_PATH.join("logs").join(format!("{}.log", config.name.replace(['/', '\\'], "_"))),
+    }
 }
 
 // 获取进程配置
@@ -164,9 +826,9 @@ pub fn get_process(name: &str) -> Option<ProcessConfig> {
     processes.get(name).cloned()
 }
 
-// 添加新进程配置
-pub fn add_process(name: &str, command: &str, dir: Option<&str>, env: Option<Vec<(String, String)>>) -> anyhow::Result<()> {
-    let config = ProcessConfig {
+// 新进程的默认配置
+pub fn new_process(name: &str, command: &str, dir: Option<&str>, env: Option<Vec<(String, String)>>) -> ProcessConfig {
+    ProcessConfig {
         start_time: Some(std::time::SystemTime::now()),
         name: name.to_string(),
         command: command.to_string(),
@@ -176,14 +838,23 @@ pub fn add_process(name: &str, command: &str, dir: Option<&str>, env: Option<Vec
         status: ProcessStatus::Unknown,
         pid: None,
         created_at: chrono::Local::now().to_rfc3339(),
-        updated_at: chrono::Local::now().to_rfc3339()
-    };
-    update_process(config)
+        updated_at: chrono::Local::now().to_rfc3339(),
+        ..Default::default()
+    }
 }
 
 // 添加或更新进程配置
 pub fn update_process(config: ProcessConfig) -> anyhow::Result<()> {
     let mut processes = PROCESSES.write().unwrap();
+    // 修改配置前先确认文件可以写入，失败时内存中的配置保持不变；
+    // 只更新运行状态时照常更新，进程实际已经启动或退出
+    let changes_settings = processes
+        .get(&config.name)
+        .map(|old| !changed_fields(old, &config).is_empty())
+        .unwrap_or(true);
+    if changes_settings {
+        ensure_writable()?;
+    }
     processes.insert(config.name.clone(), config);
     drop(processes);
     save_processes()?;
@@ -194,6 +865,7 @@ pub fn update_process(config: ProcessConfig) -> anyhow::Result<()> {
 // 删除进程配置
 pub fn remove_process(name: &str) -> anyhow::Result<()> {
     let mut processes = PROCESSES.write().unwrap();
+    ensure_writable()?;
     processes.remove(name);
     drop(processes);
     save_processes()?;
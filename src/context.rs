@@ -0,0 +1,238 @@
+use anyhow::{Context as _, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::config::SERVER_CONF;
+use crate::output::{self, OutputFormat};
+use crate::utils;
+
+// 内置的上下文名，表示本机 zapm.yaml 中配置的守护进程
+pub const LOCAL: &str = "local";
+// 选择上下文的环境变量，--context 优先
+const CONTEXT_ENV: &str = "ZAPM_CONTEXT";
+// 访问本机守护进程时使用的 API 令牌
+const TOKEN_ENV: &str = "ZAPM_TOKEN";
+
+// 远程 zapm 服务器
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Context {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+// 用户的上下文文件
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Contexts {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    #[serde(default)]
+    pub contexts: BTreeMap<String, Context>,
+}
+
+// 列出上下文时的输出，不包括令牌
+#[derive(Serialize)]
+struct ContextView<'a> {
+    name: &'a str,
+    url: &'a str,
+    current: bool,
+    token: bool,
+}
+
+// 当前命令使用的远程上下文，为 None 时访问本机的守护进程
+static ACTIVE: Lazy<RwLock<Option<(String, Context)>>> = Lazy::new(|| RwLock::new(None));
+
+// 上下文保存在用户自己的配置目录中，如 ~/.config/zapm/contexts.yaml
+fn path() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("zapm").join("contexts.yaml"))
+        .ok_or_else(|| anyhow::anyhow!("Could not find the user config directory"))
+}
+
+pub fn load() -> Result<Contexts> {
+    let path = path()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Contexts::default()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    serde_yaml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn save(contexts: &Contexts) -> Result<()> {
+    let path = path()?;
+    if let Some(dir) = path.parent() {
+        utils::ensure_dir_exists(dir)?;
+    }
+    let content = serde_yaml::to_string(contexts)?;
+
+    // 文件中保存了 API 令牌，先写入只允许用户自己读写的临时文件再替换，其他用户任何时候都读不到
+    let tmp = path.with_extension("yaml.tmp");
+    let _ = fs::remove_file(&tmp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+// 按 --context、ZAPM_CONTEXT 和 zapm context use 的顺序选择上下文，选中远程服务器时 API 请求都发往该服务器
+pub fn select(name: Option<&str>) -> Result<()> {
+    let name = name
+        .map(str::to_string)
+        .or_else(|| std::env::var(CONTEXT_ENV).ok().filter(|name| !name.is_empty()));
+    if name.as_deref() == Some(LOCAL) {
+        return Ok(());
+    }
+    let contexts = load()?;
+    let name = match name.or(contexts.current) {
+        Some(name) if name != LOCAL => name,
+        _ => return Ok(()),
+    };
+    let context = contexts
+        .contexts
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Context {} not found, add it with zapm context add", name))?;
+    SERVER_CONF.write().unwrap().api_base_url = context.url.trim_end_matches('/').to_string();
+    *ACTIVE.write().unwrap() = Some((name, context));
+    Ok(())
+}
+
+// 是否选中了远程服务器
+pub fn is_remote() -> bool {
+    ACTIVE.read().unwrap().is_some()
+}
+
+// API 请求使用的令牌：远程上下文的令牌，访问本机时为 ZAPM_TOKEN
+pub fn token() -> Option<String> {
+    match ACTIVE.read().unwrap().as_ref() {
+        Some((_, context)) => context.token.clone(),
+        None => std::env::var(TOKEN_ENV).ok().filter(|token| !token.is_empty()),
+    }
+}
+
+// 检查 URL 是否为 http(s) 地址
+fn validate_url(url: &str) -> Result<()> {
+    let rest = url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"))
+        .ok_or_else(|| anyhow::anyhow!("Invalid URL '{}', expected http:// or https://", url))?;
+    if rest.trim_end_matches('/').is_empty() {
+        return Err(anyhow::anyhow!("Invalid URL '{}', the host is missing", url));
+    }
+    Ok(())
+}
+
+// 添加或更新上下文
+pub fn add(name: &str, url: &str, token: Option<String>, make_current: bool) -> Result<()> {
+    if name.is_empty() || name == LOCAL {
+        return Err(anyhow::anyhow!("Invalid context name '{}'", name));
+    }
+    validate_url(url)?;
+    let mut contexts = load()?;
+    let existed = contexts
+        .contexts
+        .insert(
+            name.to_string(),
+            Context {
+                url: url.trim_end_matches('/').to_string(),
+                token,
+            },
+        )
+        .is_some();
+    if make_current {
+        contexts.current = Some(name.to_string());
+    }
+    save(&contexts)?;
+    println!("Context {} {}", name, if existed { "updated" } else { "added" });
+    if make_current {
+        println!("Switched to context {}", name);
+    }
+    Ok(())
+}
+
+// 切换默认使用的上下文，local 表示本机
+pub fn switch(name: &str) -> Result<()> {
+    let mut contexts = load()?;
+    if name != LOCAL && !contexts.contexts.contains_key(name) {
+        return Err(anyhow::anyhow!("Context {} not found", name));
+    }
+    contexts.current = (name != LOCAL).then(|| name.to_string());
+    save(&contexts)?;
+    println!("Switched to context {}", name);
+    Ok(())
+}
+
+// 删除上下文，删除当前的上下文时切换回本机
+pub fn remove(name: &str) -> Result<()> {
+    let mut contexts = load()?;
+    if contexts.contexts.remove(name).is_none() {
+        return Err(anyhow::anyhow!("Context {} not found", name));
+    }
+    if contexts.current.as_deref() == Some(name) {
+        contexts.current = None;
+    }
+    save(&contexts)?;
+    println!("Context {} removed", name);
+    Ok(())
+}
+
+// 列出所有上下文，包括内置的 local
+pub fn list(output: OutputFormat) -> Result<()> {
+    let contexts = load()?;
+    let current = contexts.current.as_deref().unwrap_or(LOCAL);
+    let local_url = {
+        let conf = SERVER_CONF.read().unwrap();
+        format!("http://{}:{}", conf.host, conf.port)
+    };
+    let mut views = vec![ContextView {
+        name: LOCAL,
+        url: &local_url,
+        current: current == LOCAL,
+        token: std::env::var(TOKEN_ENV).map(|token| !token.is_empty()).unwrap_or(false),
+    }];
+    views.extend(contexts.contexts.iter().map(|(name, context)| ContextView {
+        name,
+        url: &context.url,
+        current: current == name,
+        token: context.token.is_some(),
+    }));
+
+    if !output.is_human() {
+        return output::print(output, &views);
+    }
+    println!("{:<2}{:<20} {:<6} URL", "", "NAME", "TOKEN");
+    for view in views {
+        println!(
+            "{:<2}{:<20} {:<6} {}",
+            if view.current { "*" } else { "" },
+            view.name,
+            if view.token { "yes" } else { "no" },
+            view.url
+        );
+    }
+    Ok(())
+}
+
+// 显示当前使用的上下文
+pub fn current() -> Result<()> {
+    match ACTIVE.read().unwrap().as_ref() {
+        Some((name, context)) => println!("{} ({})", name, context.url),
+        None => println!("{} ({})", LOCAL, SERVER_CONF.read().unwrap().api_base_url),
+    }
+    Ok(())
+}
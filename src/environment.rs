@@ -0,0 +1,324 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::ProcessConfig;
+
+// 解析 KEY=VALUE 格式的环境变量
+pub fn parse_env_pair(pair: &str) -> Result<(String, String)> {
+    let (key, value) = pair
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Invalid environment variable '{}', expected KEY=VALUE", pair))?;
+    let key = key.trim();
+    if !is_valid_key(key) {
+        return Err(anyhow::anyhow!("Invalid environment variable name '{}'", key));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// 读取 dotenv 格式的环境变量文件
+// 单引号中的值按原样保留，其余的值会在 build_env 中做变量替换
+pub fn load_env_file<P: AsRef<Path>>(path: P) -> Result<Vec<(String, String, bool)>> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file {}", path.display()))?;
+
+    let mut vars = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = parse_env_pair(line)
+            .with_context(|| format!("{}:{}", path.display(), index + 1))?;
+        let (value, literal) = parse_env_value(value.trim());
+        vars.push((key, value, literal));
+    }
+    Ok(vars)
+}
+
+// 解析 dotenv 中的值，返回 (值, 是否为字面量)
+fn parse_env_value(value: &str) -> (String, bool) {
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return (value[1..value.len() - 1].to_string(), true);
+    }
+
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut result = String::new();
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => result.push('\n'),
+                    Some('t') => result.push('\t'),
+                    Some('r') => result.push('\r'),
+                    Some(other) => result.push(other),
+                    None => result.push('\\'),
+                }
+            } else {
+                result.push(c);
+            }
+        }
+        return (result, false);
+    }
+
+    // 未加引号的值，去掉行尾注释
+    let value = match value.find(" #") {
+        Some(pos) => value[..pos].trim_end(),
+        None => value,
+    };
+    (value.to_string(), false)
+}
+
+// 替换 ${VAR}、${VAR:-default} 和 $VAR，$$ 表示字面量 $
+pub fn interpolate<F>(value: &str, mut lookup: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                result.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let mut expr = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    expr.push(c);
+                }
+                if !closed {
+                    // 没有闭合的括号，按原样输出
+                    result.push_str("${");
+                    result.push_str(&expr);
+                    continue;
+                }
+                let (name, default) = match expr.split_once(":-") {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (expr.as_str(), None),
+                };
+                match lookup(name).filter(|v| !v.is_empty()) {
+                    Some(v) => result.push_str(&v),
+                    None => result.push_str(default.unwrap_or("")),
+                }
+            }
+            Some(&next) if next.is_ascii_alphabetic() || next == '_' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' {
+                        name.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                result.push_str(&lookup(&name).unwrap_or_default());
+            }
+            _ => result.push('$'),
+        }
+    }
+
+    result
+}
+
+// 替换 env 中的变量：env 中的变量可以相互引用，结果与声明顺序和变量名无关；
+// 引用自身（如 PATH=${PATH}:/opt/bin）或循环引用时使用之前（守护进程环境、env_file）的值
+fn resolve_env(env: &HashMap<String, String>, base: &HashMap<String, String>) -> HashMap<String, String> {
+    fn resolve_key(
+        key: &str,
+        env: &HashMap<String, String>,
+        base: &HashMap<String, String>,
+        resolved: &mut HashMap<String, String>,
+        pending: &mut Vec<String>,
+    ) -> String {
+        if let Some(value) = resolved.get(key) {
+            return value.clone();
+        }
+        pending.push(key.to_string());
+        let value = interpolate(&env[key], |name| {
+            if env.contains_key(name) && !pending.iter().any(|p| p == name) {
+                Some(resolve_key(name, env, base, resolved, pending))
+            } else {
+                base.get(name).cloned().or_else(|| std::env::var(name).ok())
+            }
+        });
+        pending.pop();
+        resolved.insert(key.to_string(), value.clone());
+        value
+    }
+
+    // 按变量名顺序开始处理，只影响循环引用的结果，使其保持稳定
+    let mut keys: Vec<&String> = env.keys().collect();
+    keys.sort();
+    let mut resolved = HashMap::new();
+    let mut pending = Vec::new();
+    for key in keys {
+        resolve_key(key, env, base, &mut resolved, &mut pending);
+    }
+    resolved
+}
+
+// 计算子进程的完整环境变量
+// 顺序：守护进程环境（受 inherit_env/env_allowlist 控制） -> env_file -> env -> 集群实例变量
+pub fn build_env(config: &ProcessConfig) -> Result<HashMap<String, String>> {
+    let mut vars: HashMap<String, String> = if config.inherit_env.unwrap_or(true) {
+        std::env::vars().collect()
+    } else {
+        let allowlist = config.env_allowlist.as_deref().unwrap_or(&[]);
+        std::env::vars()
+            .filter(|(key, _)| allowlist.iter().any(|allowed| allowed == key))
+            .collect()
+    };
+
+    // 变量替换时先查找已经确定的变量，再查找守护进程自身的环境
+    fn resolve(vars: &HashMap<String, String>, value: &str) -> String {
+        interpolate(value, |name| {
+            vars.get(name).cloned().or_else(|| std::env::var(name).ok())
+        })
+    }
+
+    if let Some(files) = &config.env_file {
+        for file in files {
+            let mut path = Path::new(file).to_path_buf();
+            if path.is_relative() {
+                if let Some(dir) = &config.working_dir {
+                    path = Path::new(dir).join(path);
+                }
+            }
+            for (key, value, literal) in load_env_file(&path)? {
+                let value = if literal { value } else { resolve(&vars, &value) };
+                vars.insert(key, value);
+            }
+        }
+    }
+
+    if let Some(env) = &config.env {
+        let resolved = resolve_env(env, &vars);
+        vars.extend(resolved);
+    }
+
+    // 集群实例的编号和端口
+    if let Some(id) = config.instance_id {
+        vars.insert("ZAPM_INSTANCE_ID".to_string(), id.to_string());
+    }
+    if let Some(port) = config.port {
+        let port = port as u32 + config.instance_id.unwrap_or(0);
+        vars.insert("PORT".to_string(), port.to_string());
+    }
+
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_env_values() {
+        let cases = [
+            ("plain", "plain", false),
+            ("value # comment", "value", false),
+            ("a#b", "a#b", false),
+            ("'single $HOME \\n'", "single $HOME \\n", true),
+            ("\"double\"", "double", false),
+            ("\"line\\nbreak\\ttab\\r\"", "line\nbreak\ttab\r", false),
+            ("\"quote \\\" inside\"", "quote \" inside", false),
+            ("\"trailing \\\\\"", "trailing \\", false),
+            ("\"keep # hash\"", "keep # hash", false),
+            ("''", "", true),
+            ("'", "'", false),
+            ("", "", false),
+        ];
+        for (input, value, literal) in cases {
+            assert_eq!(parse_env_value(input), (value.to_string(), literal), "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_env_pairs() {
+        assert_eq!(parse_env_pair("KEY=a=b").unwrap(), ("KEY".to_string(), "a=b".to_string()));
+        assert_eq!(parse_env_pair(" _K1 =").unwrap(), ("_K1".to_string(), String::new()));
+        for invalid in ["NOEQUALS", "1KEY=x", "BAD-KEY=x", "=x"] {
+            assert!(parse_env_pair(invalid).is_err(), "input {:?}", invalid);
+        }
+    }
+
+    fn env(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn resolves_references_between_env_vars() {
+        let base = env(&[("HOST", "localhost")]);
+        // B 引用 A，A 引用 B，结果都与变量名的字母顺序无关
+        let resolved = resolve_env(&env(&[("A", "http://${HOST}"), ("B", "${A}/api")]), &base);
+        assert_eq!(resolved["B"], "http://localhost/api");
+        let resolved = resolve_env(&env(&[("A", "${B}/api"), ("B", "http://${HOST}")]), &base);
+        assert_eq!(resolved["A"], "http://localhost/api");
+        // 多级引用
+        let resolved = resolve_env(&env(&[("A", "${B}!"), ("B", "${C}?"), ("C", "$HOST")]), &base);
+        assert_eq!(resolved["A"], "localhost?!");
+    }
+
+    #[test]
+    fn uses_previous_values_for_self_and_cyclic_references() {
+        let base = env(&[("ZAPM_TEST_PATH", "/usr/bin"), ("A", "old-a")]);
+        let resolved = resolve_env(&env(&[("ZAPM_TEST_PATH", "${ZAPM_TEST_PATH}:/opt/bin")]), &base);
+        assert_eq!(resolved["ZAPM_TEST_PATH"], "/usr/bin:/opt/bin");
+        // A 和 B 相互引用时，最内层的引用使用之前的值，不会无限递归
+        let resolved = resolve_env(&env(&[("A", "a${B}"), ("B", "b${A}")]), &base);
+        assert_eq!(resolved["A"], "abold-a");
+        assert_eq!(resolved["B"], "bold-a");
+    }
+
+    #[test]
+    fn interpolates_variables() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/app".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        let cases = [
+            ("$HOME/bin", "/home/app/bin"),
+            ("${HOME}/bin", "/home/app/bin"),
+            ("${MISSING}", ""),
+            ("$MISSING-x", "-x"),
+            ("${MISSING:-fallback}", "fallback"),
+            ("${EMPTY:-fallback}", "fallback"),
+            ("${HOME:-fallback}", "/home/app"),
+            ("${MISSING:-}", ""),
+            ("$$HOME", "$HOME"),
+            ("cost $5", "cost $5"),
+            ("trailing $", "trailing $"),
+            ("${HOME", "${HOME"),
+            ("no vars", "no vars"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(interpolate(input, lookup), expected, "input {:?}", input);
+        }
+    }
+}
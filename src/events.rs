@@ -0,0 +1,310 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::config::{self, ProcessConfig, CONFIG_PATH, SERVER_CONF};
+use crate::utils;
+use crate::webhooks;
+
+// API 请求中标识操作者的请求头，命令行会带上 cli:<用户名>
+pub const ACTOR_HEADER: &str = "X-Zapm-Actor";
+
+// 事件日志默认保留 30 天、最多 10000 条
+const DEFAULT_RETENTION: Duration = Duration::from_secs(30 * 24 * 3600);
+const DEFAULT_MAX_EVENTS: usize = 10000;
+// 事件总线中等待订阅者读取的事件数，订阅者落后更多时丢弃最早的事件
+const BUS_CAPACITY: usize = 256;
+// 定期清理过期事件的间隔
+const COMPACT_INTERVAL: Duration = Duration::from_secs(3600);
+
+// 进程生命周期事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Started,
+    Stopped,
+    Exited,
+    Restarted,
+    // 通过就绪检查
+    Healthy,
+    Unhealthy,
+    Errored,
+    // 添加或修改了配置
+    Updated,
+    Removed,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Started => "started",
+            EventKind::Stopped => "stopped",
+            EventKind::Exited => "exited",
+            EventKind::Restarted => "restarted",
+            EventKind::Healthy => "healthy",
+            EventKind::Unhealthy => "unhealthy",
+            EventKind::Errored => "errored",
+            EventKind::Updated => "updated",
+            EventKind::Removed => "removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub event: EventKind,
+    pub process: String,
+    pub time: String,
+    // 触发事件的操作者，如 api、cli:root、monitor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Event {
+    pub fn new(event: EventKind, process: &str) -> Self {
+        Event {
+            event,
+            process: process.to_string(),
+            time: Local::now().to_rfc3339(),
+            actor: current_actor(),
+            pid: None,
+            exit_code: None,
+            message: None,
+        }
+    }
+
+    pub fn pid(mut self, pid: u32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    pub fn exit_code(mut self, exit_code: Option<i32>) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    // 是否为该进程的事件，集群包括其实例 <name>-<id> 的事件
+    pub fn is_about(&self, name: &str) -> bool {
+        self.process == name
+            || self
+                .process
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .map(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+                .unwrap_or(false)
+    }
+}
+
+tokio::task_local! {
+    // API 请求的操作者，在请求处理期间有效
+    pub static REQUEST_ACTOR: String;
+}
+
+thread_local! {
+    // 后台线程和命令行的操作者
+    static THREAD_ACTOR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// 设置当前线程的操作者
+pub fn set_thread_actor(actor: &str) {
+    THREAD_ACTOR.with(|a| *a.borrow_mut() = Some(actor.to_string()));
+}
+
+fn current_actor() -> Option<String> {
+    REQUEST_ACTOR
+        .try_with(|actor| actor.clone())
+        .ok()
+        .or_else(|| THREAD_ACTOR.with(|a| a.borrow().clone()))
+}
+
+// 执行结束后恢复线程原来的操作者，阻塞线程池中的线程会被复用
+struct RestoreActor(Option<String>);
+
+impl Drop for RestoreActor {
+    fn drop(&mut self) {
+        THREAD_ACTOR.with(|a| *a.borrow_mut() = self.0.take());
+    }
+}
+
+// 在阻塞线程池中执行，期间产生的事件沿用当前请求或线程的操作者
+pub fn spawn_blocking<F, T>(f: F) -> tokio::task::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let actor = current_actor();
+    tokio::task::spawn_blocking(move || {
+        let _restore = RestoreActor(THREAD_ACTOR.with(|a| a.replace(actor)));
+        f()
+    })
+}
+
+// 命令行的操作者
+pub fn cli_actor() -> String {
+    match std::env::var("USER").or_else(|_| std::env::var("LOGNAME")) {
+        Ok(user) if !user.is_empty() => format!("cli:{}", user),
+        _ => "cli".to_string(),
+    }
+}
+
+// 记录进程配置的添加或修改，消息中列出变化的字段，没有变化时不记录
+pub fn record_update(old: Option<&ProcessConfig>, new: &ProcessConfig) {
+    let message = match old {
+        None => "added".to_string(),
+        Some(old) => {
+            let changed = config::changed_fields(old, new);
+            if changed.is_empty() {
+                return;
+            }
+            format!("changed {}", changed.join(", "))
+        }
+    };
+    emit(Event::new(EventKind::Updated, &new.name).message(message));
+}
+
+// 事件日志文件，每行一个 JSON 事件
+fn log_path() -> PathBuf {
+    CONFIG_PATH.join("events.jsonl")
+}
+
+fn append(event: &Event) -> Result<()> {
+    utils::ensure_dir_exists(CONFIG_PATH.as_path())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(log_path())?;
+    writeln!(file, "{}", serde_json::to_string(event)?)?;
+    Ok(())
+}
+
+// 事件队列，只在守护进程中启用
+static SENDER: Lazy<Mutex<Option<UnboundedSender<Event>>>> = Lazy::new(|| Mutex::new(None));
+
+// 事件总线，守护进程中记录的每个事件都会广播给所有订阅者
+static BUS: Lazy<broadcast::Sender<Event>> = Lazy::new(|| broadcast::channel(BUS_CAPACITY).0);
+
+// 订阅之后记录的事件
+pub fn subscribe() -> broadcast::Receiver<Event> {
+    BUS.subscribe()
+}
+
+// 记录事件，可以在任意线程中调用，不会阻塞；命令行直接操作配置时只写入事件日志
+pub fn emit(event: Event) {
+    if let Some(tx) = SENDER.lock().unwrap().as_ref() {
+        let _ = tx.send(event);
+        return;
+    }
+    if let Err(e) = append(&event) {
+        println!("Failed to record {} event of {}: {:#}", event.event.as_str(), event.process, e);
+    }
+}
+
+// 读取事件日志，process 为集群名时包括其实例的事件，since 为 Unix 时间戳
+pub fn query(process: Option<&str>, since: Option<i64>, limit: Option<usize>) -> Result<Vec<Event>> {
+    let file = match fs::File::open(log_path()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to open the event log"),
+    };
+    let mut events: Vec<Event> = BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str::<Event>(&line).ok())
+        .filter(|event| process.map(|name| event.is_about(name)).unwrap_or(true))
+        .filter(|event| match since {
+            Some(since) => chrono::DateTime::parse_from_rfc3339(&event.time)
+                .map(|t| t.timestamp() >= since)
+                .unwrap_or(false),
+            None => true,
+        })
+        .collect();
+    if let Some(limit) = limit {
+        let excess = events.len().saturating_sub(limit);
+        events.drain(..excess);
+    }
+    Ok(events)
+}
+
+// 删除超过保留时间或条数上限的事件
+fn compact() -> Result<()> {
+    let (retention, max_events) = {
+        let conf = SERVER_CONF.read().unwrap();
+        let retention = match &conf.events_retention {
+            Some(retention) => humantime::parse_duration(retention)
+                .with_context(|| format!("Invalid events_retention '{}'", retention))?,
+            None => DEFAULT_RETENTION,
+        };
+        (retention, conf.events_max.unwrap_or(DEFAULT_MAX_EVENTS))
+    };
+    let since = chrono::Utc::now().timestamp() - retention.as_secs() as i64;
+    let all = query(None, None, None)?;
+    let kept = query(None, Some(since), Some(max_events))?;
+    if kept.len() == all.len() {
+        return Ok(());
+    }
+
+    // 写入临时文件后替换，避免清理中途退出丢失事件
+    let tmp = log_path().with_extension("jsonl.tmp");
+    let mut file = fs::File::create(&tmp)?;
+    for event in &kept {
+        writeln!(file, "{}", serde_json::to_string(event)?)?;
+    }
+    file.sync_all()?;
+    fs::rename(&tmp, log_path())?;
+    Ok(())
+}
+
+// 启动事件分发：写入事件日志、发送 webhook 并广播给订阅者，需要在 tokio 运行时中调用
+pub fn start() {
+    if let Err(e) = compact() {
+        println!("Failed to compact the event log: {:#}", e);
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    *SENDER.lock().unwrap() = Some(tx);
+    tokio::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(webhooks::REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        let mut compact_timer = tokio::time::interval(COMPACT_INTERVAL);
+        compact_timer.tick().await;
+        loop {
+            tokio::select! {
+                event = rx.recv() => {
+                    let event = match event {
+                        Some(event) => event,
+                        None => break,
+                    };
+                    if let Err(e) = append(&event) {
+                        println!("Failed to record {} event of {}: {:#}", event.event.as_str(), event.process, e);
+                    }
+                    webhooks::dispatch(&client, &event);
+                    let _ = BUS.send(event);
+                }
+                _ = compact_timer.tick() => {
+                    if let Err(e) = compact() {
+                        println!("Failed to compact the event log: {:#}", e);
+                    }
+                }
+            }
+        }
+    });
+}
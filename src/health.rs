@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use crate::config::{get_process, HealthCheck, ProcessConfig};
+use crate::environment;
+use crate::events::{self, Event, EventKind};
+use crate::metrics;
+use crate::process;
+use crate::user;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+// 单次检查的超时时间
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+// 没有配置就绪检查时，进程持续运行这么久即视为就绪
+const DEFAULT_GRACE: Duration = Duration::from_secs(2);
+
+fn parse_duration(value: Option<&str>, default: Duration) -> Result<Duration> {
+    match value {
+        Some(value) => humantime::parse_duration(value).with_context(|| format!("Invalid duration '{}'", value)),
+        None => Ok(default),
+    }
+}
+
+// 校验就绪检查配置
+pub fn validate(check: &HealthCheck) -> Result<()> {
+    parse_duration(check.timeout.as_deref(), DEFAULT_TIMEOUT)?;
+    parse_duration(check.interval.as_deref(), DEFAULT_INTERVAL)?;
+    Ok(())
+}
+
+// 执行一次就绪检查
+async fn check_once(
+    check: &HealthCheck,
+    config: &ProcessConfig,
+    env: &HashMap<String, String>,
+    credentials: &Option<user::Credentials>,
+) -> Result<()> {
+    let expand = |value: &str| environment::interpolate(value, |name| env.get(name).cloned());
+
+    if let Some(url) = &check.http {
+        let url = expand(url);
+        let response = reqwest::Client::builder()
+            .timeout(CHECK_TIMEOUT)
+            .build()?
+            .get(&url)
+            .send()
+            .await
+            .with_context(|| format!("GET {} failed", url))?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("GET {} returned {}", url, response.status()));
+        }
+    }
+
+    if let Some(addr) = &check.tcp {
+        let addr = expand(addr);
+        tokio::time::timeout(CHECK_TIMEOUT, tokio::net::TcpStream::connect(&addr))
+            .await
+            .map_err(|_| anyhow::anyhow!("Connecting to {} timed out", addr))?
+            .with_context(|| format!("Failed to connect to {}", addr))?;
+    }
+
+    if let Some(command) = &check.command {
+        let command = expand(command);
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        if parts.is_empty() {
+            return Err(anyhow::anyhow!("Empty health check command"));
+        }
+        // 与进程使用相同的运行用户和资源限制；不加入进程的 cgroup，准备 cgroup 会重写运行中进程的限制
+        let mut cmd = std::process::Command::new(parts[0]);
+        cmd.args(&parts[1..]).env_clear().envs(env).stdin(Stdio::null());
+        if let Some(dir) = &config.working_dir {
+            cmd.current_dir(dir);
+        }
+        process::run_as(&mut cmd, config, credentials.clone())?;
+        let mut cmd = tokio::process::Command::from(cmd);
+        cmd.kill_on_drop(true);
+        let status = tokio::time::timeout(CHECK_TIMEOUT, cmd.status())
+            .await
+            .map_err(|_| anyhow::anyhow!("Health check command '{}' timed out", command))?
+            .with_context(|| format!("Failed to run health check command '{}'", command))?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("Health check command '{}' exited with {}", command, status));
+        }
+    }
+
+    Ok(())
+}
+
+// 等待进程就绪：进程退出或超时未通过检查时返回错误，并发送 unhealthy 事件；配置了检查的进程通过后发送 healthy 事件
+pub async fn wait_ready(name: &str) -> Result<()> {
+    ready(name, None).await
+}
+
+// 等待平滑替换时启动的新进程就绪，此时进程配置中的 PID 仍是旧进程
+pub async fn wait_pid_ready(name: &str, pid: u32) -> Result<()> {
+    ready(name, Some(pid)).await
+}
+
+async fn ready(name: &str, pid: Option<u32>) -> Result<()> {
+    let result = wait(name, pid).await;
+    match &result {
+        Ok(()) => {
+            if get_process(name).map(|c| c.health_check.is_some()).unwrap_or(false) {
+                events::emit(Event::new(EventKind::Healthy, name));
+            }
+        }
+        Err(e) => {
+            metrics::record_health_check_failure(name);
+            events::emit(Event::new(EventKind::Unhealthy, name).message(format!("{:#}", e)));
+        }
+    }
+    result
+}
+
+async fn wait(name: &str, pid: Option<u32>) -> Result<()> {
+    let config = get_process(name).ok_or_else(|| anyhow::anyhow!("Process {} not found", name))?;
+    let check = config.health_check.clone().unwrap_or_default();
+    let timeout = parse_duration(check.timeout.as_deref(), DEFAULT_TIMEOUT)?;
+    let interval = parse_duration(check.interval.as_deref(), DEFAULT_INTERVAL)?;
+    let has_check = check.http.is_some() || check.tcp.is_some() || check.command.is_some();
+    let (env, credentials) = process::process_env(&config)?;
+
+    let started = Instant::now();
+    let mut last_error = None;
+    loop {
+        let pid = pid.or_else(|| get_process(name).and_then(|c| c.pid));
+        if !pid.map(process::is_alive).unwrap_or(false) {
+            return Err(anyhow::anyhow!("Process {} exited before becoming ready", name));
+        }
+
+        if has_check {
+            match check_once(&check, &config, &env, &credentials).await {
+                Ok(_) => return Ok(()),
+                Err(e) => last_error = Some(e),
+            }
+        } else if started.elapsed() >= DEFAULT_GRACE {
+            return Ok(());
+        }
+
+        if started.elapsed() >= timeout {
+            return Err(match last_error {
+                Some(e) => e.context(format!("Process {} did not become ready within {:?}", name, timeout)),
+                None => anyhow::anyhow!("Process {} did not become ready within {:?}", name, timeout),
+            });
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+
+use crate::config::{self, Hooks, ProcessConfig};
+use crate::environment;
+use crate::process;
+#[cfg(unix)]
+use crate::user;
+use crate::utils;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Hook {
+    PreStart,
+    PostStart,
+    PreStop,
+    PostStop,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::PreStart => "pre_start",
+            Hook::PostStart => "post_start",
+            Hook::PreStop => "pre_stop",
+            Hook::PostStop => "post_stop",
+        }
+    }
+
+    fn command(self, hooks: &Hooks) -> Option<&String> {
+        match self {
+            Hook::PreStart => hooks.pre_start.as_ref(),
+            Hook::PostStart => hooks.post_start.as_ref(),
+            Hook::PreStop => hooks.pre_stop.as_ref(),
+            Hook::PostStop => hooks.post_stop.as_ref(),
+        }
+    }
+}
+
+fn timeout(hooks: &Hooks) -> Result<Duration> {
+    match &hooks.timeout {
+        Some(timeout) => humantime::parse_duration(timeout).with_context(|| format!("Invalid hook timeout '{}'", timeout)),
+        None => Ok(DEFAULT_TIMEOUT),
+    }
+}
+
+// 校验钩子配置
+pub fn validate(hooks: &Hooks) -> Result<()> {
+    timeout(hooks)?;
+    for hook in [Hook::PreStart, Hook::PostStart, Hook::PreStop, Hook::PostStop] {
+        if hook.command(hooks).map(|c| c.trim().is_empty()).unwrap_or(false) {
+            return Err(anyhow::anyhow!("The {} hook command is empty", hook.name()));
+        }
+    }
+    Ok(())
+}
+
+// pre_start 失败时是否中止启动
+pub fn aborts_start(config: &ProcessConfig) -> bool {
+    config.hooks.as_ref().and_then(|h| h.abort_on_failure).unwrap_or(true)
+}
+
+// 执行钩子并等待结束，未配置时直接返回；输出追加到进程的日志文件
+pub fn run(config: &ProcessConfig, hook: Hook) -> Result<()> {
+    let hooks = match &config.hooks {
+        Some(hooks) => hooks,
+        None => return Ok(()),
+    };
+    let command = match hook.command(hooks) {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+    let timeout = timeout(hooks)?;
+
+    let (env, credentials) = process::process_env(config)?;
+    let expanded = environment::interpolate(command, |name| env.get(name).cloned());
+    let parts: Vec<&str> = expanded.split_whitespace().collect();
+    if parts.is_empty() {
+        return Err(anyhow::anyhow!("The {} hook command of process {} is empty", hook.name(), config.name));
+    }
+
+    let log_path = config::log_path(config);
+    if let Some(dir) = log_path.parent() {
+        utils::ensure_dir_exists(dir)?;
+    }
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("Failed to open log file {}", log_path.display()))?;
+    writeln!(log, "[zapm] running {} hook: {}", hook.name(), expanded)?;
+
+    let mut cmd = Command::new(parts[0]);
+    cmd.args(&parts[1..])
+        .env_clear()
+        .envs(&env)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log.try_clone()?);
+    if let Some(dir) = &config.working_dir {
+        cmd.current_dir(dir);
+    }
+    #[cfg(unix)]
+    if let Some(creds) = credentials {
+        unsafe {
+            cmd.pre_exec(move || user::apply(&creds));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = credentials;
+
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to run {} hook of process {}", hook.name(), config.name))?;
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = writeln!(log, "[zapm] {} hook timed out after {:?}", hook.name(), timeout);
+            return Err(anyhow::anyhow!(
+                "The {} hook of process {} timed out after {:?}",
+                hook.name(),
+                config.name,
+                timeout
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+
+    if !status.success() {
+        let _ = writeln!(log, "[zapm] {} hook failed: {}", hook.name(), status);
+        return Err(anyhow::anyhow!("The {} hook of process {} failed: {}", hook.name(), config.name, status));
+    }
+    Ok(())
+}
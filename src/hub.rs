@@ -0,0 +1,192 @@
+use axum::{
+    body::{self, Body},
+    http::{header, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Local;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::auth::{self, Identity};
+use crate::config::{HostConf, SERVER_CONF};
+use crate::events;
+use crate::process::ProcessView;
+
+// 查询 agent 状态的超时时间
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 访问 agent 的客户端，不设置总超时以便转发事件流
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+});
+
+// 每个 agent 最近一次正常响应的时间
+static LAST_SEEN: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// agent 的状态及其进程
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HostStatus {
+    pub name: String,
+    pub url: String,
+    pub up: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
+    #[serde(default)]
+    pub processes: Vec<ProcessView>,
+}
+
+// 通过 zapm server --hub 或 hub.enabled 启用
+pub fn enabled() -> bool {
+    SERVER_CONF.read().unwrap().hub.as_ref().map(|hub| hub.enabled).unwrap_or(false)
+}
+
+fn hosts() -> Vec<HostConf> {
+    SERVER_CONF.read().unwrap().hub.as_ref().map(|hub| hub.hosts.clone()).unwrap_or_default()
+}
+
+// 发往 agent 的 API 请求，带上 agent 的令牌和当前请求的操作者
+fn agent_request(host: &HostConf, method: Method, path: &str) -> reqwest::RequestBuilder {
+    let url = format!("{}/api/{}", host.url.trim_end_matches('/'), path.trim_start_matches('/'));
+    let mut builder = CLIENT.request(method, url);
+    if let Some(token) = &host.token {
+        builder = builder.bearer_auth(token);
+    }
+    builder
+}
+
+async fn fetch_processes(host: &HostConf) -> anyhow::Result<Vec<ProcessView>> {
+    let response = agent_request(host, Method::GET, "processes")
+        .query(&[("view", "true")])
+        .timeout(STATUS_TIMEOUT)
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow::anyhow!("HTTP {}: {}", status.as_u16(), response.text().await.unwrap_or_default()));
+    }
+    let processes: HashMap<String, ProcessView> = response.json().await?;
+    let mut processes: Vec<ProcessView> = processes.into_values().collect();
+    processes.sort_by(|a, b| a.config.name.cmp(&b.config.name));
+    Ok(processes)
+}
+
+async fn check(host: HostConf) -> HostStatus {
+    let started = Instant::now();
+    let result = fetch_processes(&host).await;
+    let mut last_seen = LAST_SEEN.lock().unwrap();
+    let (up, error, processes) = match result {
+        Ok(processes) => {
+            last_seen.insert(host.name.clone(), Local::now().to_rfc3339());
+            (true, None, processes)
+        }
+        // reqwest 的错误链很长，只保留根本原因
+        Err(e) => (false, Some(e.root_cause().to_string()), Vec::new()),
+    };
+    HostStatus {
+        latency_ms: up.then(|| started.elapsed().as_millis() as u64),
+        last_seen: last_seen.get(&host.name).cloned(),
+        name: host.name,
+        url: host.url,
+        up,
+        error,
+        processes,
+    }
+}
+
+// 同时查询所有 agent 的状态和进程
+pub async fn status() -> Vec<HostStatus> {
+    let handles: Vec<_> = hosts().into_iter().map(|host| tokio::spawn(check(host))).collect();
+    let mut statuses = Vec::with_capacity(handles.len());
+    for handle in handles {
+        if let Ok(status) = handle.await {
+            statuses.push(status);
+        }
+    }
+    statuses
+}
+
+// 把请求转发到 agent 的 /api/<path>，响应以流的形式返回，事件流也可以代理
+pub async fn proxy(name: &str, path: &str, request: Request<Body>) -> Response {
+    let host = match hosts().into_iter().find(|host| host.name == name) {
+        Some(host) => host,
+        None => return (StatusCode::NOT_FOUND, format!("Host {} not found", name)).into_response(),
+    };
+    let path = match request.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let method = request.method().clone();
+    let content_type = request.headers().get(header::CONTENT_TYPE).cloned();
+    let identity = request.extensions().get::<Identity>();
+    let masked = !auth::can_view_env(identity);
+    let actor = identity.map(Identity::actor);
+    let payload = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(payload) => payload,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    let mut builder = agent_request(&host, method, &path).body(payload);
+    // 只转发 hub 上认证过的操作者，agent 通过令牌认证 hub 后记为 hub:<操作者>
+    if let Some(actor) = actor {
+        builder = builder.header(events::ACTOR_HEADER, actor);
+    }
+    if let Some(content_type) = content_type {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    let mut response = match builder.send().await {
+        Ok(response) => response,
+        Err(e) => return (StatusCode::BAD_GATEWAY, format!("Host {} is unreachable: {}", name, e)).into_response(),
+    };
+    // 401 会让 Web 界面以为 hub 的会话已失效，改为 502
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return (StatusCode::BAD_GATEWAY, format!("Host {} rejected the hub's token", name)).into_response();
+    }
+
+    // agent 使用 hub 的令牌，返回的环境变量值需要按 hub 上请求者的角色隐藏
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with("application/json"))
+        .unwrap_or(false);
+    if masked && is_json {
+        let status = response.status();
+        return match response.json::<serde_json::Value>().await {
+            Ok(mut body) => {
+                auth::mask_env(&mut body);
+                (status, Json(body)).into_response()
+            }
+            Err(e) => (StatusCode::BAD_GATEWAY, format!("Invalid response from host {}: {}", name, e)).into_response(),
+        };
+    }
+
+    let mut proxied = Response::builder().status(response.status());
+    for key in [header::CONTENT_TYPE, header::CACHE_CONTROL] {
+        if let Some(value) = response.headers().get(&key) {
+            proxied = proxied.header(key, value.clone());
+        }
+    }
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        while let Ok(Some(chunk)) = response.chunk().await {
+            if sender.send_data(chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+    proxied
+        .body(body::boxed(body))
+        .unwrap_or_else(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())
+}
@@ -1,18 +1,48 @@
+mod auth;
+#[cfg(target_os = "linux")]
+mod cgroup;
+mod cluster;
 mod config;
+mod context;
 mod daemon;
+mod environment;
+mod events;
+mod health;
+mod hooks;
+mod hub;
+mod metrics;
+mod monit;
+mod output;
 mod process;
+mod reload;
+mod rlimit;
+mod schedule;
 mod server;
+#[cfg(unix)]
+mod sockets;
+mod user;
 mod utils;
+mod validate;
+mod watch;
+mod webhooks;
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use output::OutputFormat;
 
 #[derive(Parser)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// 输出格式
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+    /// 使用的上下文 (远程服务器)，覆盖 zapm context use 的选择，local 为本机
+    #[arg(long, global = true)]
+    context: Option<String>,
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     Service {
         action : String
@@ -24,7 +54,9 @@ enum Commands {
         host : String,
         #[arg(short, long, default_value_t = 0)]
         port: u16,
-
+        /// 集中管理模式，汇总并代理 zapm.yaml 中 hub.hosts 配置的守护进程
+        #[arg(long)]
+        hub: bool,
     },
     /// 添加进程
     Add {
@@ -39,6 +71,114 @@ enum Commands {
         /// 环境变量 (格式: KEY=VALUE)
         #[arg(short, long)]
         env: Vec<String>,
+        /// 环境变量文件 (dotenv 格式)
+        #[arg(long)]
+        env_file: Vec<String>,
+        /// 不继承守护进程的环境变量
+        #[arg(long)]
+        no_inherit_env: bool,
+        /// 不继承环境变量时仍然传递的变量名
+        #[arg(long)]
+        env_allow: Vec<String>,
+        /// 运行进程的用户
+        #[arg(long)]
+        user: Option<String>,
+        /// 运行进程的组
+        #[arg(long)]
+        group: Option<String>,
+        /// 附加组
+        #[arg(long = "groups", value_delimiter = ',')]
+        groups: Vec<String>,
+        /// 资源限制 (格式: NAME=LIMIT 或 NAME=SOFT:HARD，如 nofile=65536)
+        #[arg(long)]
+        rlimit: Vec<String>,
+        /// cgroup 内存上限 (如 512M)
+        #[arg(long)]
+        memory_max: Option<String>,
+        /// cgroup CPU 权重 (1-10000)
+        #[arg(long)]
+        cpu_weight: Option<u32>,
+        /// cgroup CPU 上限 (如 50% 或 "50000 100000")
+        #[arg(long)]
+        cpu_max: Option<String>,
+        /// cgroup 最大任务数
+        #[arg(long)]
+        pids_max: Option<u64>,
+        /// 内存超过该值时自动重启 (如 512M)
+        #[arg(long)]
+        max_memory: Option<String>,
+        /// CPU 使用率持续超过该百分比时自动重启
+        #[arg(long)]
+        max_cpu: Option<f32>,
+        /// CPU 超限持续时间 (默认 60s)
+        #[arg(long)]
+        max_cpu_duration: Option<String>,
+        /// 停止时等待进程退出的时间 (默认 5s)
+        #[arg(long)]
+        kill_timeout: Option<String>,
+        /// 日志文件 (默认为配置目录下的 logs/<name>.log)
+        #[arg(long)]
+        log_file: Option<String>,
+        /// 集群模式的实例数 (数字或 max)
+        #[arg(short, long)]
+        instances: Option<String>,
+        /// 注入的 PORT 环境变量，集群模式下每个实例依次加 1
+        #[arg(long)]
+        port: Option<u16>,
+        /// 就绪检查的 URL，GET 返回 2xx 即为就绪 (如 http://127.0.0.1:${PORT}/health)
+        #[arg(long)]
+        health_http: Option<String>,
+        /// 就绪检查的 TCP 地址 (如 127.0.0.1:${PORT})
+        #[arg(long)]
+        health_tcp: Option<String>,
+        /// 就绪检查命令，退出码为 0 即为就绪
+        #[arg(long)]
+        health_cmd: Option<String>,
+        /// 等待就绪的最长时间 (默认 30s)
+        #[arg(long)]
+        health_timeout: Option<String>,
+        /// 进程类型
+        #[arg(long = "type", value_enum)]
+        process_type: Option<config::ProcessType>,
+        /// cron 表达式，支持可选的秒字段 (如 "*/5 * * * *")
+        #[arg(long)]
+        schedule: Option<String>,
+        /// 上一次运行尚未结束时仍然按计划启动新的运行
+        #[arg(long)]
+        allow_overlap: bool,
+        /// 按 cron 表达式定时重启 (如 "0 3 * * *")
+        #[arg(long)]
+        cron_restart: Option<String>,
+        /// 启动前执行的命令 (如数据库迁移)
+        #[arg(long)]
+        pre_start: Option<String>,
+        /// 启动后执行的命令
+        #[arg(long)]
+        post_start: Option<String>,
+        /// 停止前执行的命令 (如清空缓存)
+        #[arg(long)]
+        pre_stop: Option<String>,
+        /// 停止后执行的命令
+        #[arg(long)]
+        post_stop: Option<String>,
+        /// 钩子命令的超时时间 (默认 60s)
+        #[arg(long)]
+        hook_timeout: Option<String>,
+        /// pre_start 失败时仍然启动进程
+        #[arg(long)]
+        ignore_pre_start_failure: bool,
+        /// 文件变化时自动重启，不指定路径时监视工作目录
+        #[arg(long, num_args = 0..)]
+        watch: Option<Vec<String>>,
+        /// 监视时忽略的文件 (glob，如 "*.log")，可多次指定
+        #[arg(long)]
+        ignore: Vec<String>,
+        /// 文件变化后等待多久再重启 (默认 1s)
+        #[arg(long)]
+        watch_delay: Option<String>,
+        /// 由 zapm 持有并通过 LISTEN_FDS 传给进程的监听地址 (tcp://host:port 或 unix:///path)，可多次指定
+        #[arg(long)]
+        listen: Vec<String>,
         /// 自动重启
         #[arg(short, long)]
         auto_restart: bool,
@@ -54,6 +194,18 @@ enum Commands {
         /// 进程名称
         name: String,
     },
+    /// 滚动重启，逐个重启实例并等待就绪
+    Reload {
+        /// 进程名称
+        name: String,
+    },
+    /// 调整集群的实例数
+    Scale {
+        /// 进程名称
+        name: String,
+        /// 实例数 (数字或 max)
+        instances: String,
+    },
     /// 列出所有进程
     List,
     /// 显示进程状态
@@ -66,6 +218,42 @@ enum Commands {
         /// 进程名称
         name: String,
     },
+    /// 查看进程的历史资源使用情况
+    Metrics {
+        /// 进程名称
+        name: String,
+        /// 起始时间 (如 1h、RFC 3339 时间或 Unix 时间戳)
+        #[arg(long)]
+        since: Option<String>,
+        /// 聚合间隔 (如 5m)
+        #[arg(long)]
+        step: Option<String>,
+    },
+    /// 查看进程日志
+    Logs {
+        /// 进程名称
+        name: String,
+        /// 显示的行数
+        #[arg(short = 'n', long, default_value_t = 100)]
+        lines: usize,
+    },
+    /// 查看事件日志
+    Events {
+        /// 进程名称，集群包括其实例，不指定时显示所有进程
+        name: Option<String>,
+        /// 起始时间 (如 1h、RFC 3339 时间或 Unix 时间戳)
+        #[arg(long)]
+        since: Option<String>,
+        /// 最多显示的条数
+        #[arg(short = 'n', long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// 交互式监控面板
+    Monit,
+    /// 查看 hub 管理的守护进程及其进程
+    Hosts,
+    /// 生成 auth.users 中使用的密码哈希，从标准输入读取密码
+    HashPassword,
     /// 移除进程
     Remove {
         /// 进程名称
@@ -74,6 +262,68 @@ enum Commands {
         #[arg(short, long)]
         force: bool,
     },
+    /// 管理远程服务器的上下文
+    Context {
+        #[command(subcommand)]
+        action: ContextAction,
+    },
+    /// 检查配置文件
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// 检查配置文件，不指定文件时检查 processes.yaml 和 zapm.yaml
+    Validate {
+        /// 配置文件，名为 zapm.yaml 时按服务器配置检查，否则按进程配置检查
+        file: Option<String>,
+    },
+    /// 输出配置文件的 JSON Schema
+    Schema {
+        /// 输出 zapm.yaml 的 Schema，默认为 processes.yaml
+        #[arg(long)]
+        server: bool,
+    },
+    /// 让守护进程重新加载 processes.yaml 和 zapm.yaml，不需要重启守护进程
+    Reload,
+}
+
+#[derive(Subcommand)]
+enum ContextAction {
+    /// 添加或更新上下文
+    Add {
+        /// 上下文名称
+        name: String,
+        /// 服务器地址 (如 https://zapm.example.com)
+        #[arg(long)]
+        url: String,
+        /// API 令牌
+        #[arg(long)]
+        token: Option<String>,
+        /// 从标准输入读取 API 令牌，避免出现在命令历史中
+        #[arg(long, conflicts_with = "token")]
+        token_stdin: bool,
+        /// 添加后切换到该上下文
+        #[arg(long = "use")]
+        make_current: bool,
+    },
+    /// 切换默认使用的上下文，local 为本机
+    Use {
+        /// 上下文名称
+        name: String,
+    },
+    /// 列出所有上下文
+    List,
+    /// 显示当前使用的上下文
+    Current,
+    /// 删除上下文
+    Remove {
+        /// 上下文名称
+        name: String,
+    },
 }
 
 
@@ -85,6 +335,21 @@ async fn main() -> Result<()> {
 
     // 解析命令行参数
     let cli = Cli::parse();
+    // 命令行直接修改配置时记录操作者，守护进程按请求记录
+    if !matches!(cli.command, Commands::Server { .. } | Commands::Service { .. }) {
+        events::set_thread_actor(&events::cli_actor());
+    }
+    // 守护进程只管理本机；管理上下文时不要求当前上下文有效
+    let uses_context = match &cli.command {
+        Commands::Server { .. } | Commands::Service { .. } | Commands::HashPassword => false,
+        Commands::Config { action } => matches!(action, ConfigAction::Reload),
+        Commands::Context { action } => matches!(action, ContextAction::Current),
+        _ => true,
+    };
+    if uses_context {
+        context::select(cli.context.as_deref())?;
+    }
+    let remote = context::is_remote();
     // 处理命令
     match &cli.command {
         Commands::Service { action  } => {
@@ -102,60 +367,355 @@ async fn main() -> Result<()> {
             }
             
         }
-        Commands::Server { host ,port } => {
-            // 通过 Web API 启动服务
-            if !host.is_empty() {
-                config::SERVER_CONF.write().unwrap().host = String::from(host);
-            }
-            if *port != 0 {
-                config::SERVER_CONF.write().unwrap().port = *port;
-            }
+        Commands::Server { host ,port, hub } => {
+            // 通过 Web API 启动服务，命令行参数在重新加载配置后仍然生效
+            let overrides = config::ServerOverrides {
+                host: (!host.is_empty()).then(|| host.clone()),
+                port: (*port != 0).then_some(*port),
+                hub: *hub,
+            };
+            overrides.apply(&mut config::SERVER_CONF.write().unwrap());
+            *config::SERVER_OVERRIDES.write().unwrap() = overrides;
 
-            server::start_server( config::SERVER_CONF.read().unwrap().host.as_str(),config::SERVER_CONF.read().unwrap().port).await?;
+            // 不能在服务器运行期间持有读锁，重新加载配置时需要写入
+            let (host, port) = {
+                let conf = config::SERVER_CONF.read().unwrap();
+                (conf.host.clone(), conf.port)
+            };
+            server::start_server(&host, port).await?;
         }
         Commands::Add {
             name,
             cmd,
             dir,
             env,
+            env_file,
+            no_inherit_env,
+            env_allow,
+            user,
+            group,
+            groups,
+            rlimit,
+            memory_max,
+            cpu_weight,
+            cpu_max,
+            pids_max,
+            max_memory,
+            max_cpu,
+            max_cpu_duration,
+            kill_timeout,
+            log_file,
+            instances,
+            port,
+            health_http,
+            health_tcp,
+            health_cmd,
+            health_timeout,
+            listen,
+            process_type,
+            schedule,
+            allow_overlap,
+            cron_restart,
+            watch,
+            ignore,
+            watch_delay,
+            pre_start,
+            post_start,
+            pre_stop,
+            post_stop,
+            hook_timeout,
+            ignore_pre_start_failure,
             auto_restart,
         } => {
-            crate::config::add_process(name, cmd, dir.as_deref(), Some(env.iter().map(|e| (e.clone(), e.clone())).collect()))?;
-            
-            // 更新自动重启设置
-            if let Some(mut config) = config::get_process(name) {
-                config.auto_restart = *auto_restart;
-                config::update_process(config)?;
+            let health_check = if health_http.is_some() || health_tcp.is_some() || health_cmd.is_some() {
+                let check = config::HealthCheck {
+                    http: health_http.clone(),
+                    tcp: health_tcp.clone(),
+                    command: health_cmd.clone(),
+                    timeout: health_timeout.clone(),
+                    interval: None,
+                };
+                health::validate(&check)?;
+                Some(check)
+            } else {
+                None
+            };
+            let instances = instances.as_deref().map(cluster::parse_instances).transpose()?;
+            let hooks = if pre_start.is_some() || post_start.is_some() || pre_stop.is_some() || post_stop.is_some() {
+                let hooks = config::Hooks {
+                    pre_start: pre_start.clone(),
+                    post_start: post_start.clone(),
+                    pre_stop: pre_stop.clone(),
+                    post_stop: post_stop.clone(),
+                    timeout: hook_timeout.clone(),
+                    abort_on_failure: ignore_pre_start_failure.then_some(false),
+                };
+                hooks::validate(&hooks)?;
+                Some(hooks)
+            } else {
+                None
+            };
+            // 只指定 schedule 时默认为 cron 任务
+            let process_type = process_type.unwrap_or(if schedule.is_some() {
+                config::ProcessType::Cron
+            } else {
+                config::ProcessType::Service
+            });
+            schedule::validate(&config::ProcessConfig {
+                name: name.clone(),
+                process_type,
+                schedule: schedule.clone(),
+                cron_restart: cron_restart.clone(),
+                instances: instances.clone(),
+                ..Default::default()
+            })?;
+            let watch = watch.as_ref().map(|paths| {
+                if paths.is_empty() {
+                    config::WatchValue::Enabled(true)
+                } else {
+                    config::WatchValue::Paths(paths.clone())
+                }
+            });
+            watch::validate(&config::ProcessConfig {
+                name: name.clone(),
+                working_dir: dir.clone(),
+                watch: watch.clone(),
+                ignore: (!ignore.is_empty()).then(|| ignore.clone()),
+                watch_delay: watch_delay.clone(),
+                ..Default::default()
+            })?;
+            #[cfg(unix)]
+            for address in listen {
+                sockets::validate(address)?;
+            }
+            let env = env.iter()
+                .map(|e| environment::parse_env_pair(e))
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(max_memory) = max_memory {
+                utils::parse_memory_size(max_memory)?;
+            }
+            for duration in [max_cpu_duration, kill_timeout].into_iter().flatten() {
+                humantime::parse_duration(duration)?;
+            }
+            let rlimits = if rlimit.is_empty() {
+                None
+            } else {
+                let mut rlimits = std::collections::HashMap::new();
+                for limit in rlimit {
+                    let (key, value) = limit.split_once('=').ok_or_else(|| {
+                        anyhow::anyhow!("Invalid rlimit '{}', expected NAME=LIMIT", limit)
+                    })?;
+                    let value = match value.parse::<u64>() {
+                        Ok(n) => config::RlimitValue::Number(n),
+                        Err(_) => config::RlimitValue::Text(value.to_string()),
+                    };
+                    rlimits.insert(key.to_string(), value);
+                }
+                rlimit::parse(&rlimits)?;
+                Some(rlimits)
+            };
+            let mut config = config::new_process(name, cmd, dir.as_deref(), Some(env));
+            // 设置自动重启和环境变量等
+            config.auto_restart = *auto_restart;
+            if !env_file.is_empty() {
+                config.env_file = Some(env_file.clone());
+            }
+            if *no_inherit_env {
+                config.inherit_env = Some(false);
+            }
+            if !env_allow.is_empty() {
+                config.env_allowlist = Some(env_allow.clone());
+            }
+            config.user = user.clone();
+            config.group = group.clone();
+            if !groups.is_empty() {
+                config.groups = Some(groups.clone());
+            }
+            config.rlimits = rlimits.clone();
+            config.memory_max = memory_max.clone();
+            config.cpu_weight = *cpu_weight;
+            config.cpu_max = cpu_max.clone();
+            config.pids_max = *pids_max;
+            config.max_memory = max_memory.clone();
+            config.max_cpu_percent = *max_cpu;
+            config.max_cpu_duration = max_cpu_duration.clone();
+            config.kill_timeout = kill_timeout.clone();
+            config.log_file = log_file.clone();
+            config.instances = instances;
+            config.port = *port;
+            config.health_check = health_check;
+            if !listen.is_empty() {
+                config.listen = Some(listen.clone());
+            }
+            config.process_type = process_type;
+            config.schedule = schedule.clone();
+            config.allow_overlap = allow_overlap.then_some(true);
+            config.cron_restart = cron_restart.clone();
+            config.watch = watch;
+            if !ignore.is_empty() {
+                config.ignore = Some(ignore.clone());
+            }
+            config.watch_delay = watch_delay.clone();
+            config.hooks = hooks;
+            if remote {
+                utils::update_process_via_api(&config).await?;
+            } else {
+                let previous = config::get_process(name);
+                config::update_process(config.clone())?;
+                events::record_update(previous.as_ref(), &config);
             }
             
-            println!("Process {} added", name);
+            print_result(name, "added", cli.output).await?;
         }
         Commands::Start { name } => {
-            utils::start_process_via_api(name).await?;            
-            // println!("Process {} started", name);
+            let message = utils::start_process_via_api(name).await?;
+            if cli.output.is_human() {
+                println!("Process {} started , {}", name, message);
+            } else {
+                output::print(cli.output, &utils::get_process_via_api(name, false).await?)?;
+            }
         }
         Commands::Stop { name } => {
-            process::stop_process(name)?;
-            println!("Process {} stopped", name);
+            if remote {
+                utils::stop_process_via_api(name).await?;
+            } else {
+                process::stop_process(name)?;
+            }
+            print_result(name, "stopped", cli.output).await?;
         }
         Commands::Restart { name } => {
-            process::restart_process(name)?;
-            println!("Process {} restarted", name);
+            if remote {
+                utils::restart_process_via_api(name).await?;
+            } else {
+                process::restart_process(name)?;
+            }
+            print_result(name, "restarted", cli.output).await?;
+        }
+        Commands::Reload { name } => {
+            utils::reload_process_via_api(name).await?;
+            print_result(name, "reloaded", cli.output).await?;
+        }
+        Commands::Scale { name, instances } => {
+            let instances = cluster::parse_instances(instances)?;
+            utils::scale_process_via_api(name, &instances).await?;
+            if cli.output.is_human() {
+                println!("Process {} scaled to {} instances", name, cluster::resolve_count(&instances)?);
+            } else {
+                output::print(cli.output, &utils::get_process_via_api(name, false).await?)?;
+            }
+        }
+        Commands::List | Commands::Status { name: None } if remote => {
+            process::print_list(utils::list_processes_via_api().await?, cli.output)?;
         }
         Commands::List => {
-            process::list_processes()?;
+            process::list_processes(cli.output)?;
+        }
+        Commands::Status { name: Some(name) } if remote => {
+            process::print_status(&utils::get_process_via_api(name, false).await?, cli.output)?;
         }
         Commands::Status { name } => {
-            process::show_status(name.as_deref())?;
+            process::show_status(name.as_deref(), cli.output)?;
+        }
+        Commands::Show { name } if remote => {
+            process::print_details(&utils::get_process_via_api(name, true).await?, cli.output)?;
         }
         Commands::Show { name } => {
-            process::show_details(&name)?;
+            process::show_details(name, cli.output)?;
+        }
+        Commands::Metrics { name, since, step } => {
+            utils::show_metrics_via_api(name, since.as_deref(), step.as_deref(), cli.output).await?;
+        }
+        Commands::Logs { name, lines } => {
+            utils::show_logs_via_api(name, *lines).await?;
+        }
+        Commands::Events { name, since, limit } => {
+            utils::show_events_via_api(name.as_deref(), since.as_deref(), *limit, cli.output).await?;
+        }
+        Commands::Monit => {
+            monit::run().await?;
+        }
+        Commands::Hosts => {
+            utils::show_hosts_via_api(cli.output).await?;
+        }
+        Commands::HashPassword => {
+            let mut password = String::new();
+            std::io::stdin().read_line(&mut password)?;
+            let password = password.trim_end_matches(['\r', '\n']);
+            if password.is_empty() {
+                return Err(anyhow::anyhow!("The password is empty"));
+            }
+            println!("{}", auth::hash_password(password)?);
+        }
+        Commands::Remove { name, force } if remote => {
+            let removed = match cli.output.is_human() {
+                true => None,
+                false => Some(utils::get_process_via_api(name, false).await?),
+            };
+            utils::delete_process_via_api(name, *force).await?;
+            match removed {
+                Some(view) => output::print(cli.output, &view)?,
+                None => println!("Process {} removed{}", name, if *force { " (force)" } else { "" }),
+            }
         }
         Commands::Remove { name, force } => {
-            process::remove_process(&name, *force)?;
-            println!("Process {} removed{}", name, if *force { " (force)" } else { "" });
+            let removed = config::get_process(name);
+            process::remove_process(name, *force)?;
+            match removed {
+                Some(config) if !cli.output.is_human() => process::print_process(config, cli.output)?,
+                _ => println!("Process {} removed{}", name, if *force { " (force)" } else { "" }),
+            }
         }
+        Commands::Context { action } => match action {
+            ContextAction::Add { name, url, token, token_stdin, make_current } => {
+                let token = if *token_stdin {
+                    let mut token = String::new();
+                    std::io::stdin().read_line(&mut token)?;
+                    Some(token.trim().to_string()).filter(|token| !token.is_empty())
+                } else {
+                    token.clone()
+                };
+                context::add(name, url, token, *make_current)?;
+            }
+            ContextAction::Use { name } => context::switch(name)?,
+            ContextAction::List => context::list(cli.output)?,
+            ContextAction::Current => context::current()?,
+            ContextAction::Remove { name } => context::remove(name)?,
+        },
+        Commands::Config { action } => match action {
+            ConfigAction::Validate { file: Some(file) } => {
+                validate::run(&[std::path::Path::new(file)], cli.output)?;
+            }
+            ConfigAction::Validate { file: None } => {
+                let server_path = config::CONFIG_PATH.join("zapm.yaml");
+                validate::run(&[config::PROCESS_CONFIG_PATH.as_path(), server_path.as_path()], cli.output)?;
+            }
+            ConfigAction::Schema { server } => {
+                let schema = if *server { config::server_schema() } else { config::processes_schema() };
+                println!("{}", serde_json::to_string_pretty(&schema)?);
+            }
+            ConfigAction::Reload => {
+                let report = utils::reload_config_via_api().await?;
+                if cli.output.is_human() {
+                    report.print();
+                } else {
+                    output::print(cli.output, &report)?;
+                }
+            }
+        },
     }
 
     Ok(())
+}
+
+// 输出操作结果，JSON/YAML 格式时输出进程当前的配置，使用远程上下文时从服务器获取
+async fn print_result(name: &str, action: &str, output: OutputFormat) -> Result<()> {
+    if output.is_human() {
+        println!("Process {} {}", name, action);
+        return Ok(());
+    }
+    if context::is_remote() {
+        return output::print(output, &utils::get_process_via_api(name, false).await?);
+    }
+    let config = config::get_process(name).ok_or_else(|| anyhow::anyhow!("Process {} not found", name))?;
+    process::print_process(config, output)
 }
\ No newline at end of file
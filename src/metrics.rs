@@ -0,0 +1,381 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use sysinfo::{PidExt, ProcessExt, System, SystemExt};
+
+use crate::config::{ProcessConfig, ProcessStatus, CONFIG_PATH, PROCESSES, SERVER_CONF};
+
+// 守护进程启动时间
+pub static DAEMON_STARTED: Lazy<Instant> = Lazy::new(Instant::now);
+
+// 采集指标使用的系统信息，保留上一次采样才能计算 CPU 使用率
+static METRICS_SYSTEM: Lazy<Mutex<System>> = Lazy::new(|| Mutex::new(System::new()));
+
+// 每个进程就绪检查失败的次数，守护进程重启后清零
+static HEALTH_CHECK_FAILURES: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// 记录一次就绪检查失败
+pub fn record_health_check_failure(name: &str) {
+    *HEALTH_CHECK_FAILURES.lock().unwrap().entry(name.to_string()).or_default() += 1;
+}
+
+// 单个进程的资源使用情况
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+    pub virtual_memory_bytes: u64,
+    pub open_fds: Option<u64>,
+    pub threads: Option<u64>,
+}
+
+// 统计 /proc/<pid>/fd 中的文件描述符数量
+fn open_fds(pid: u32) -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        std::fs::read_dir(format!("/proc/{}/fd", pid))
+            .ok()
+            .map(|entries| entries.count() as u64)
+    } else {
+        None
+    }
+}
+
+// 读取 /proc/<pid>/status 中的线程数
+fn threads(pid: u32) -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("Threads:"))
+            .and_then(|v| v.trim().parse().ok())
+    } else {
+        None
+    }
+}
+
+pub fn resource_usage(process: &sysinfo::Process) -> ResourceUsage {
+    let pid = process.pid().as_u32();
+    ResourceUsage {
+        cpu_percent: process.cpu_usage(),
+        memory_bytes: process.memory(),
+        virtual_memory_bytes: process.virtual_memory(),
+        open_fds: open_fds(pid),
+        threads: threads(pid),
+    }
+}
+
+// 当前所有运行中进程的资源使用情况
+pub fn current_usage() -> HashMap<String, ResourceUsage> {
+    let mut system = METRICS_SYSTEM.lock().unwrap();
+    system.refresh_processes();
+
+    let processes = PROCESSES.read().unwrap();
+    processes
+        .values()
+        .filter(|config| config.status == ProcessStatus::Running)
+        .filter_map(|config| {
+            let process = system.process(sysinfo::Pid::from(config.pid? as usize))?;
+            Some((config.name.clone(), resource_usage(process)))
+        })
+        .collect()
+}
+
+// 转义标签值中的反斜杠、引号和换行
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn labels(config: &ProcessConfig) -> String {
+    format!("name=\"{}\",group=\"{}\"", escape(&config.name), escape(config.group_name()))
+}
+
+// 按指标输出 HELP/TYPE 以及每个进程的样本
+fn write_metric<F>(out: &mut String, name: &str, kind: &str, help: &str, processes: &[&ProcessConfig], mut value: F)
+where
+    F: FnMut(&ProcessConfig) -> Option<f64>,
+{
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for config in processes {
+        if let Some(v) = value(config) {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels(config), v);
+        }
+    }
+}
+
+// 生成 Prometheus 文本格式的指标
+pub fn render() -> String {
+    let mut system = METRICS_SYSTEM.lock().unwrap();
+    system.refresh_processes();
+
+    // 集群本身只是模板，指标来自各个实例（group 标签为集群名）
+    let processes = PROCESSES.read().unwrap();
+    let mut configs: Vec<&ProcessConfig> = processes.values().filter(|c| !crate::cluster::is_parent(c)).collect();
+    configs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    // 每个进程只读取一次 /proc
+    let usages: HashMap<&str, ResourceUsage> = configs
+        .iter()
+        .filter(|config| config.status == ProcessStatus::Running)
+        .filter_map(|config| {
+            let process = system.process(sysinfo::Pid::from(config.pid? as usize))?;
+            Some((config.name.as_str(), resource_usage(process)))
+        })
+        .collect();
+    let usage = |config: &ProcessConfig| usages.get(config.name.as_str()).copied();
+    let failures = HEALTH_CHECK_FAILURES.lock().unwrap().clone();
+
+    let mut out = String::new();
+
+    write_metric(&mut out, "zapm_process_up", "gauge", "Whether the process is running (1) or not (0).", &configs, |c| {
+        Some(if usage(c).is_some() { 1.0 } else { 0.0 })
+    });
+    write_metric(&mut out, "zapm_process_cpu_percent", "gauge", "CPU usage of the process in percent of one core.", &configs, |c| {
+        usage(c).map(|u| u.cpu_percent as f64)
+    });
+    write_metric(&mut out, "zapm_process_resident_memory_bytes", "gauge", "Resident memory size in bytes.", &configs, |c| {
+        usage(c).map(|u| u.memory_bytes as f64)
+    });
+    write_metric(&mut out, "zapm_process_virtual_memory_bytes", "gauge", "Virtual memory size in bytes.", &configs, |c| {
+        usage(c).map(|u| u.virtual_memory_bytes as f64)
+    });
+    write_metric(&mut out, "zapm_process_open_fds", "gauge", "Number of open file descriptors.", &configs, |c| {
+        usage(c).and_then(|u| u.open_fds).map(|v| v as f64)
+    });
+    write_metric(&mut out, "zapm_process_threads", "gauge", "Number of OS threads in the process.", &configs, |c| {
+        usage(c).and_then(|u| u.threads).map(|v| v as f64)
+    });
+    write_metric(&mut out, "zapm_process_uptime_seconds", "gauge", "Seconds since the process was started.", &configs, |c| {
+        if c.status != ProcessStatus::Running {
+            return None;
+        }
+        c.start_time
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .map(|d| d.as_secs_f64())
+    });
+    write_metric(&mut out, "zapm_process_restarts_total", "counter", "Number of times the process has been restarted.", &configs, |c| {
+        Some(c.restarts as f64)
+    });
+    write_metric(&mut out, "zapm_process_crashes_total", "counter", "Number of times the process exited unexpectedly.", &configs, |c| {
+        Some(c.crashes as f64)
+    });
+    write_metric(&mut out, "zapm_process_health_check_failures_total", "counter", "Number of times the process failed to become ready.", &configs, |c| {
+        Some(failures.get(&c.name).copied().unwrap_or(0) as f64)
+    });
+
+    // 守护进程自身的指标
+    let _ = writeln!(out, "# HELP zapm_daemon_uptime_seconds Seconds since the zapm daemon was started.");
+    let _ = writeln!(out, "# TYPE zapm_daemon_uptime_seconds gauge");
+    let _ = writeln!(out, "zapm_daemon_uptime_seconds {}", DAEMON_STARTED.elapsed().as_secs_f64());
+
+    if let Some(daemon) = sysinfo::get_current_pid().ok().and_then(|pid| system.process(pid)) {
+        let _ = writeln!(out, "# HELP zapm_daemon_resident_memory_bytes Resident memory size of the zapm daemon in bytes.");
+        let _ = writeln!(out, "# TYPE zapm_daemon_resident_memory_bytes gauge");
+        let _ = writeln!(out, "zapm_daemon_resident_memory_bytes {}", daemon.memory());
+        let _ = writeln!(out, "# HELP zapm_daemon_cpu_percent CPU usage of the zapm daemon in percent of one core.");
+        let _ = writeln!(out, "# TYPE zapm_daemon_cpu_percent gauge");
+        let _ = writeln!(out, "zapm_daemon_cpu_percent {}", daemon.cpu_usage());
+    }
+
+    let _ = writeln!(out, "# HELP zapm_managed_processes Number of managed processes by status.");
+    let _ = writeln!(out, "# TYPE zapm_managed_processes gauge");
+    for status in [
+        ProcessStatus::Running,
+        ProcessStatus::Stopped,
+        ProcessStatus::Failed,
+        ProcessStatus::Completed,
+        ProcessStatus::Unknown,
+    ] {
+        let count = configs.iter().filter(|c| c.status == status).count();
+        let _ = writeln!(out, "zapm_managed_processes{{status=\"{:?}\"}} {}", status, count);
+    }
+
+    let _ = writeln!(out, "# HELP zapm_build_info zapm version information.");
+    let _ = writeln!(out, "# TYPE zapm_build_info gauge");
+    let _ = writeln!(out, "zapm_build_info{{version=\"{}\"}} 1", env!("CARGO_PKG_VERSION"));
+
+    out
+}
+
+const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_RETENTION: Duration = Duration::from_secs(6 * 3600);
+// 开启持久化时，每隔多少次采样写一次文件
+const PERSIST_EVERY: u64 = 6;
+
+// 一次资源采样
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    // Unix 时间戳（秒）
+    pub timestamp: i64,
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+    pub virtual_memory_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_fds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u64>,
+}
+
+// 每个进程的采样环形缓冲区
+static SAMPLES: Lazy<Mutex<HashMap<String, VecDeque<Sample>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn conf_duration(value: Option<String>, default: Duration) -> Duration {
+    value
+        .and_then(|v| humantime::parse_duration(&v).ok())
+        .filter(|d| !d.is_zero())
+        .unwrap_or(default)
+}
+
+// 采样间隔
+pub fn sample_interval() -> Duration {
+    conf_duration(SERVER_CONF.read().unwrap().metrics_interval.clone(), DEFAULT_SAMPLE_INTERVAL)
+}
+
+// 每个进程保留的采样数
+fn capacity() -> usize {
+    let retention = conf_duration(SERVER_CONF.read().unwrap().metrics_retention.clone(), DEFAULT_RETENTION);
+    ((retention.as_secs() / sample_interval().as_secs().max(1)) as usize).max(1)
+}
+
+fn persist_enabled() -> bool {
+    SERVER_CONF.read().unwrap().metrics_persist.unwrap_or(false)
+}
+
+fn persist_path() -> std::path::PathBuf {
+    CONFIG_PATH.join("metrics.json")
+}
+
+// 从 metrics.json 恢复采样
+fn load_samples() {
+    let content = match fs::read_to_string(persist_path()) {
+        Ok(content) => content,
+        Err(_) => return,
+    };
+    match serde_json::from_str::<HashMap<String, VecDeque<Sample>>>(&content) {
+        Ok(samples) => *SAMPLES.lock().unwrap() = samples,
+        Err(e) => eprintln!("Warning: Failed to load metrics history: {}", e),
+    }
+}
+
+fn save_samples() -> anyhow::Result<()> {
+    let content = serde_json::to_string(&*SAMPLES.lock().unwrap())?;
+    fs::write(persist_path(), content)?;
+    Ok(())
+}
+
+// 采样一次所有运行中的进程
+fn collect_samples(system: &mut System) {
+    system.refresh_processes();
+
+    let processes = PROCESSES.read().unwrap();
+    let mut samples = SAMPLES.lock().unwrap();
+    let capacity = capacity();
+    let now = chrono::Utc::now().timestamp();
+
+    // 删除已经不存在的进程
+    samples.retain(|name, _| processes.contains_key(name));
+
+    for (name, config) in processes.iter() {
+        if config.status != ProcessStatus::Running {
+            continue;
+        }
+        let process = match config.pid.and_then(|pid| system.process(sysinfo::Pid::from(pid as usize))) {
+            Some(process) => process,
+            None => continue,
+        };
+        let usage = resource_usage(process);
+        let buffer = samples.entry(name.clone()).or_default();
+        buffer.push_back(Sample {
+            timestamp: now,
+            cpu_percent: usage.cpu_percent,
+            memory_bytes: usage.memory_bytes,
+            virtual_memory_bytes: usage.virtual_memory_bytes,
+            open_fds: usage.open_fds,
+            threads: usage.threads,
+        });
+        while buffer.len() > capacity {
+            buffer.pop_front();
+        }
+    }
+}
+
+// 定期采样进程资源使用情况
+pub fn start_sampler() {
+    if persist_enabled() {
+        load_samples();
+    }
+
+    std::thread::spawn(|| {
+        let mut system = System::new();
+        // 第一次刷新只作为计算 CPU 使用率的基准
+        system.refresh_processes();
+        let mut count: u64 = 0;
+        loop {
+            std::thread::sleep(sample_interval());
+            collect_samples(&mut system);
+
+            count += 1;
+            if persist_enabled() && count.is_multiple_of(PERSIST_EVERY) {
+                if let Err(e) = save_samples() {
+                    eprintln!("Warning: Failed to save metrics history: {}", e);
+                }
+            }
+        }
+    });
+}
+
+// 查询进程的历史采样，step 大于 0 时按时间段求平均
+pub fn query(name: &str, since: Option<i64>, step: Option<u64>) -> Vec<Sample> {
+    let samples = SAMPLES.lock().unwrap();
+    let selected = samples
+        .get(name)
+        .map(|buffer| {
+            buffer
+                .iter()
+                .filter(|s| since.map(|since| s.timestamp >= since).unwrap_or(true))
+                .cloned()
+                .collect::<Vec<Sample>>()
+        })
+        .unwrap_or_default();
+
+    let step = match step {
+        Some(step) if step > 0 => step as i64,
+        _ => return selected,
+    };
+
+    let mut result: Vec<Sample> = Vec::new();
+    let mut bucket: Vec<&Sample> = Vec::new();
+    let flush = |bucket: &mut Vec<&Sample>, result: &mut Vec<Sample>| {
+        if bucket.is_empty() {
+            return;
+        }
+        let n = bucket.len() as u64;
+        let average = |values: Vec<u64>| values.iter().sum::<u64>() / n;
+        let optional = |values: Vec<Option<u64>>| values.into_iter().max().flatten();
+        result.push(Sample {
+            timestamp: bucket[0].timestamp - bucket[0].timestamp.rem_euclid(step),
+            cpu_percent: bucket.iter().map(|s| s.cpu_percent).sum::<f32>() / n as f32,
+            memory_bytes: average(bucket.iter().map(|s| s.memory_bytes).collect()),
+            virtual_memory_bytes: average(bucket.iter().map(|s| s.virtual_memory_bytes).collect()),
+            open_fds: optional(bucket.iter().map(|s| s.open_fds).collect()),
+            threads: optional(bucket.iter().map(|s| s.threads).collect()),
+        });
+        bucket.clear();
+    };
+
+    for sample in &selected {
+        if let Some(first) = bucket.first() {
+            if sample.timestamp.div_euclid(step) != first.timestamp.div_euclid(step) {
+                flush(&mut bucket, &mut result);
+            }
+        }
+        bucket.push(sample);
+    }
+    flush(&mut bucket, &mut result);
+    result
+}
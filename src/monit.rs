@@ -0,0 +1,280 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::execute;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use std::collections::HashMap;
+use std::io::stdout;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::{ProcessConfig, ProcessStatus, SERVER_CONF};
+use crate::metrics::ResourceUsage;
+use crate::utils;
+
+// 刷新间隔
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+// 监控面板状态
+struct App {
+    client: reqwest::Client,
+    api_base_url: String,
+    processes: Vec<ProcessConfig>,
+    usage: HashMap<String, ResourceUsage>,
+    table: TableState,
+    log: String,
+    log_lines: u16,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+// 退出时恢复终端，包括出错返回的情况
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(stdout(), LeaveAlternateScreen);
+    }
+}
+
+impl App {
+    fn selected(&self) -> Option<&ProcessConfig> {
+        self.table.selected().and_then(|i| self.processes.get(i))
+    }
+
+    // 从守护进程拉取进程列表、资源使用情况和选中进程的日志
+    async fn refresh(&mut self) {
+        match self.fetch().await {
+            Ok(_) => self.error = None,
+            Err(e) => self.error = Some(format!("{:#}", e)),
+        }
+    }
+
+    async fn fetch(&mut self) -> Result<()> {
+        let selected = self.selected().map(|p| p.name.clone());
+
+        let processes: HashMap<String, ProcessConfig> = self
+            .client
+            .get(format!("{}/api/processes", self.api_base_url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let mut processes: Vec<ProcessConfig> = processes.into_values().collect();
+        processes.sort_by(|a, b| a.name.cmp(&b.name));
+        self.processes = processes;
+
+        self.usage = self
+            .client
+            .get(format!("{}/api/stats", self.api_base_url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // 刷新后保持选中同一个进程
+        let index = selected
+            .and_then(|name| self.processes.iter().position(|p| p.name == name))
+            .or(if self.processes.is_empty() { None } else { Some(0) })
+            .map(|i| i.min(self.processes.len().saturating_sub(1)));
+        self.table.select(index);
+
+        self.log = match self.selected() {
+            Some(process) => self
+                .client
+                .get(format!("{}/api/processes/{}/logs", self.api_base_url, utils::path_segment(&process.name)))
+                .query(&[("lines", self.log_lines.max(1))])
+                .send()
+                .await?
+                .text()
+                .await?,
+            None => String::new(),
+        };
+        Ok(())
+    }
+
+    // 对选中的进程执行 start/stop/restart
+    async fn action(&mut self, action: &str) {
+        let name = match self.selected() {
+            Some(process) => process.name.clone(),
+            None => return,
+        };
+        let result = self
+            .client
+            .post(format!("{}/api/processes/{}/{}", self.api_base_url, utils::path_segment(&name), action))
+            .json(&serde_json::json!({}))
+            .send()
+            .await;
+        self.message = Some(match result {
+            Ok(response) if response.status().is_success() => format!("{}: {} ok", name, action),
+            Ok(response) => format!("{}: {} failed: {}", name, action, response.text().await.unwrap_or_default()),
+            Err(e) => format!("{}: {} failed: {}", name, action, e),
+        });
+    }
+
+    fn select_next(&mut self, offset: isize) {
+        if self.processes.is_empty() {
+            return;
+        }
+        let len = self.processes.len() as isize;
+        let current = self.table.selected().unwrap_or(0) as isize;
+        self.table.select(Some((current + offset).rem_euclid(len) as usize));
+    }
+}
+
+// 运行交互式监控面板
+pub async fn run() -> Result<()> {
+    let api_base_url = SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let mut app = App {
+        client: utils::api_client_builder().timeout(Duration::from_secs(5)).build()?,
+        api_base_url,
+        processes: Vec::new(),
+        usage: HashMap::new(),
+        table: TableState::default(),
+        log: String::new(),
+        log_lines: 20,
+        message: None,
+        error: None,
+    };
+
+    // 启动前先确认守护进程可以访问，避免进入全屏后才报错
+    app.fetch().await?;
+
+    enable_raw_mode()?;
+    let _guard = TerminalGuard;
+    execute!(stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let mut last_refresh = Instant::now();
+    loop {
+        terminal.draw(|frame| draw(frame, &mut app))?;
+
+        let timeout = REFRESH_INTERVAL.saturating_sub(last_refresh.elapsed());
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                let changed = match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.select_next(-1);
+                        true
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.select_next(1);
+                        true
+                    }
+                    KeyCode::Char('s') => {
+                        app.action("start").await;
+                        true
+                    }
+                    KeyCode::Char('x') => {
+                        app.action("stop").await;
+                        true
+                    }
+                    KeyCode::Char('r') => {
+                        app.action("restart").await;
+                        true
+                    }
+                    _ => false,
+                };
+                if !changed {
+                    continue;
+                }
+            }
+        }
+
+        app.refresh().await;
+        last_refresh = Instant::now();
+    }
+
+    Ok(())
+}
+
+fn status_style(status: &ProcessStatus) -> Style {
+    match status {
+        ProcessStatus::Running => Style::default().fg(Color::Green),
+        ProcessStatus::Failed => Style::default().fg(Color::Red),
+        ProcessStatus::Stopped => Style::default().fg(Color::DarkGray),
+        ProcessStatus::Completed => Style::default().fg(Color::Cyan),
+        _ => Style::default().fg(Color::Yellow),
+    }
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let table_height = (app.processes.len() as u16 + 3).min(frame.size().height / 2).max(4);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(table_height), Constraint::Min(3), Constraint::Length(1)])
+        .split(frame.size());
+
+    let now = SystemTime::now();
+    let rows: Vec<Row> = app
+        .processes
+        .iter()
+        .map(|process| {
+            let usage = app.usage.get(&process.name);
+            let uptime = match (&process.status, process.start_time) {
+                (ProcessStatus::Running, Some(start)) => {
+                    utils::format_uptime(now.duration_since(start).unwrap_or_default().as_secs())
+                }
+                _ => "-".to_string(),
+            };
+            Row::new(vec![
+                Cell::from(process.name.clone()),
+                Cell::from(format!("{:?}", process.status)).style(status_style(&process.status)),
+                Cell::from(process.pid.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string())),
+                Cell::from(usage.map(|u| format!("{:.1}%", u.cpu_percent)).unwrap_or_else(|| "-".to_string())),
+                Cell::from(
+                    usage
+                        .map(|u| utils::format_memory_size(u.memory_bytes / 1024))
+                        .unwrap_or_else(|| "-".to_string()),
+                ),
+                Cell::from(process.restarts.to_string()),
+                Cell::from(uptime),
+            ])
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Min(16),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Length(12),
+        Constraint::Length(9),
+        Constraint::Length(14),
+    ];
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["NAME", "STATUS", "PID", "CPU", "MEMORY", "RESTARTS", "UPTIME"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(" zapm monit "))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, chunks[0], &mut app.table);
+
+    // 日志面板只显示能放下的最后几行
+    app.log_lines = chunks[1].height.saturating_sub(2);
+    let title = match app.selected() {
+        Some(process) => format!(" logs: {} ", process.name),
+        None => " logs ".to_string(),
+    };
+    let log = Paragraph::new(app.log.as_str()).block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(log, chunks[1]);
+
+    let footer = match (&app.error, &app.message) {
+        (Some(error), _) => Line::styled(format!(" error: {}", error), Style::default().fg(Color::Red)),
+        (None, Some(message)) => Line::raw(format!(" {}", message)),
+        (None, None) => Line::styled(
+            " ↑/↓ select  s start  x stop  r restart  q quit",
+            Style::default().fg(Color::DarkGray),
+        ),
+    };
+    frame.render_widget(Paragraph::new(footer), chunks[2]);
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+// 命令行输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// 表格
+    Table,
+    /// 包含更多列的表格
+    Wide,
+    /// JSON
+    Json,
+    /// YAML
+    Yaml,
+}
+
+impl OutputFormat {
+    pub fn is_human(self) -> bool {
+        matches!(self, OutputFormat::Table | OutputFormat::Wide)
+    }
+}
+
+// 以 JSON 或 YAML 输出，表格格式由各命令自行打印
+pub fn print<T: Serialize + ?Sized>(format: OutputFormat, value: &T) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        OutputFormat::Table | OutputFormat::Wide => {
+            return Err(anyhow::anyhow!("{:?} output must be printed by the command itself", format))
+        }
+    }
+    Ok(())
+}
@@ -1,21 +1,50 @@
 use crate::config::{self,get_process, update_process, ProcessConfig, ProcessStatus};
+#[cfg(target_os = "linux")]
+use crate::cgroup;
+use crate::cluster;
+use crate::environment;
+use crate::events::{self, Event, EventKind};
+use crate::health;
+use crate::hooks::{self, Hook};
+use crate::metrics::{self, ResourceUsage};
+use crate::output::{self, OutputFormat};
+use crate::rlimit;
+use crate::schedule;
+#[cfg(unix)]
+use crate::sockets;
+use crate::user;
+use crate::utils;
+use crate::watch;
 use anyhow::{Context, Result};
 use chrono::Local;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::io::{Read, Seek, SeekFrom};
 use std::process::{Child, Command};
 use std::sync::{Mutex};
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
 use sysinfo::{ProcessExt, System, SystemExt};
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 
 #[cfg(target_os = "windows")]
 use winapi::um::winbase::{CREATE_NO_WINDOW};
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
 // 运行中的进程
 static RUNNING_PROCESSES: Lazy<Mutex<HashMap<String, Child>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// 监控使用的系统信息，保留上一次采样才能计算 CPU 使用率
+static MONITOR_SYSTEM: Lazy<Mutex<System>> = Lazy::new(|| Mutex::new(System::new()));
+// CPU 使用率开始超过 max_cpu_percent 的时间
+static CPU_OVER_LIMIT_SINCE: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+const DEFAULT_KILL_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_CPU_DURATION: Duration = Duration::from_secs(60);
 
 // 启动进程
 pub fn start_process(
@@ -24,6 +53,11 @@ pub fn start_process(
     working_dir: Option<&str>,
     env_vars: Option<&Vec<String>>,
 ) -> Result<()> {
+    // 集群模式的进程只是模板，启动它的所有实例
+    if get_process(name).filter(cluster::is_parent).is_some() {
+        return cluster::start(name);
+    }
+
     // 检查进程是否已存在
     if let Some(config) = get_process(name) {
         let mut system = System::new_all();
@@ -54,12 +88,71 @@ pub fn start_process(
         }
     }
 
-    // 解析命令和参数
-    let parts: Vec<&str> = cmd.split_whitespace().collect();
-    if parts.is_empty() {
+    if cmd.split_whitespace().next().is_none() {
         return Err(anyhow::anyhow!("Empty command"));
     }
 
+    // 以已有配置为基础，保留 auto_restart、env_file 等设置
+    let now = Local::now().to_rfc3339();
+    let mut config = get_process(name).unwrap_or_else(|| ProcessConfig {
+        name: name.to_string(),
+        created_at: now.clone(),
+        ..Default::default()
+    });
+    config.command = cmd.to_string();
+    config.working_dir = working_dir.map(|s| s.to_string());
+    if let Some(env_list) = env_vars {
+        let mut map = HashMap::new();
+        for env_var in env_list {
+            let (key, value) = environment::parse_env_pair(env_var)?;
+            map.insert(key, value);
+        }
+        config.env = Some(map);
+    }
+
+    // 启动前执行 pre_start 钩子，失败时默认中止启动
+    if let Err(e) = hooks::run(&config, Hook::PreStart) {
+        if hooks::aborts_start(&config) {
+            return Err(e.context(format!("Process {} was not started", name)));
+        }
+        println!("{:#}; starting process {} anyway", e, name);
+    }
+
+    let child = spawn(&config, false)?;
+
+    let pid = child.id();
+
+    // 更新进程状态
+    config.start_time = Some(SystemTime::now());
+    config.status = ProcessStatus::Running;
+    config.pid = Some(pid);
+    config.updated_at = now;
+
+    update_process(config)?;
+
+    // 保存运行中的进程
+    let mut running = RUNNING_PROCESSES.lock().unwrap();
+    running.insert(name.to_string(), child);
+
+    drop(running);
+
+    println!("Process {} started with PID {}", name, pid);
+    events::emit(Event::new(EventKind::Started, name).pid(pid));
+    if let Some(config) = get_process(name) {
+        if let Err(e) = hooks::run(&config, Hook::PostStart) {
+            println!("{:#}", e);
+        }
+    }
+    Ok(())
+}
+
+// 按配置启动进程，输出追加到日志文件，不修改进程状态
+fn spawn(config: &ProcessConfig, own_group: bool) -> Result<Child> {
+    let name = config.name.as_str();
+    let parts: Vec<&str> = config.command.split_whitespace().collect();
+    if parts.is_empty() {
+        return Err(anyhow::anyhow!("Empty command"));
+    }
     let program = parts[0];
     let args = &parts[1..];
 
@@ -68,101 +161,240 @@ pub fn start_process(
     command.args(args);
     #[cfg(windows)]
     command.creation_flags(CREATE_NO_WINDOW);
+    // 平滑替换时新进程自成进程组，未能就绪时可以连同它的子进程一起终止
+    #[cfg(unix)]
+    if own_group {
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = own_group;
 
     // 设置工作目录
-    if let Some(dir) = working_dir {
+    if let Some(dir) = &config.working_dir {
         command.current_dir(dir);
     }
 
-    // 设置环境变量
-    if let Some(env_list) = env_vars {
-        for env_var in env_list {
-            if let Some((key, value)) = env_var.split_once('=') {
-                command.env(key, value);
-            }
+    // 标准输出和标准错误追加到日志文件
+    let log_path = config::log_path(config);
+    if let Some(dir) = log_path.parent() {
+        utils::ensure_dir_exists(dir)?;
+    }
+    rotate_log(&log_path)?;
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("Failed to open log file {}", log_path.display()))?;
+    command.stdout(log.try_clone()?);
+    command.stderr(log);
+
+    // 设置环境变量，切换运行用户和组
+    let (env, credentials) = process_env(config)?;
+
+    command.env_clear();
+    command.envs(&env);
+
+    confine(&mut command, config, credentials)?;
+
+    // 传递监听套接字（systemd LISTEN_FDS 协议），必须是最后一个 pre_exec，因为它会直接 execve
+    #[cfg(unix)]
+    if let Some(mut exec) = sockets::prepare(config, program, args, &env)? {
+        unsafe {
+            command.pre_exec(move || exec.exec());
         }
     }
+    #[cfg(not(unix))]
+    if config.listen.as_ref().map(|l| !l.is_empty()).unwrap_or(false) {
+        return Err(anyhow::anyhow!("Process {} declares listen sockets, which are only supported on Unix", name));
+    }
 
     // 启动进程
-    let child = command
-        .spawn()
-        .with_context(|| format!("Failed to start process {}", name))?;
+    command.spawn().with_context(|| format!("Failed to start process {}", name))
+}
 
-    let pid = child.id();
+// 在 exec 之前加入进程的 cgroup、设置资源限制并切换运行用户
+pub fn confine(command: &mut Command, config: &ProcessConfig, credentials: Option<user::Credentials>) -> Result<()> {
+    // 加入进程自己的 cgroup，需要在切换用户之前完成
+    #[cfg(target_os = "linux")]
+    if let Some(procs) = cgroup::prepare(config)? {
+        unsafe {
+            command.pre_exec(move || cgroup::join(&procs));
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    if config.memory_max.is_some() || config.cpu_weight.is_some() || config.cpu_max.is_some() || config.pids_max.is_some() {
+        return Err(anyhow::anyhow!("Process {} sets cgroup limits, which are only supported on Linux", config.name));
+    }
+    run_as(command, config, credentials)
+}
 
-    // 更新进程状态
-    let now = Local::now().to_rfc3339();
-    let mut env_map = None;
-    if let Some(env_list) = env_vars {
-        let mut map = HashMap::new();
-        for env_var in env_list {
-            if let Some((key, value)) = env_var.split_once('=') {
-                map.insert(key.to_string(), value.to_string());
-            }
+// 设置资源限制并切换运行身份，不加入 cgroup，用于健康检查命令等不应修改进程 cgroup 的场合
+pub fn run_as(command: &mut Command, config: &ProcessConfig, credentials: Option<user::Credentials>) -> Result<()> {
+    // 资源限制需要在切换用户之前设置，才能以 root 身份提高硬限制
+    let limits = match &config.rlimits {
+        Some(rlimits) => rlimit::parse(rlimits)?,
+        None => Vec::new(),
+    };
+    #[cfg(target_os = "linux")]
+    if !limits.is_empty() {
+        unsafe {
+            command.pre_exec(move || rlimit::apply(&limits));
         }
-        env_map = Some(map);
     }
 
-    let config = ProcessConfig {
-        start_time: Some(SystemTime::now()),
+    #[cfg(unix)]
+    if let Some(creds) = credentials {
+        unsafe {
+            command.pre_exec(move || user::apply(&creds));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = credentials;
+    Ok(())
+}
 
-        name: name.to_string(),
-        command: cmd.to_string(),
-        working_dir: working_dir.map(|s| s.to_string()),
-        env: env_map,
-        auto_restart: false,
-        status: ProcessStatus::Running,
-        pid: Some(pid),
-        created_at: now.clone(),
-        updated_at: now,
+// 校验进程配置，返回出错的字段和原因，Web 界面在对应的输入框旁显示
+pub fn validate_fields(config: &ProcessConfig) -> Vec<(&'static str, String)> {
+    let mut errors = Vec::new();
+    let mut check = |field: &'static str, result: Result<()>| {
+        if let Err(e) = result {
+            errors.push((field, format!("{:#}", e)));
+        }
+    };
+    let duration = |value: &Option<String>| -> Result<()> {
+        if let Some(value) = value {
+            humantime::parse_duration(value).with_context(|| format!("Invalid duration '{}'", value))?;
+        }
+        Ok(())
     };
 
-    update_process(config)?;
+    if config.command.trim().is_empty() {
+        check("command", Err(anyhow::anyhow!("The command is empty")));
+    }
+    // cron_restart 单独校验，使错误显示在对应的字段上
+    let without_restart = ProcessConfig { cron_restart: None, ..config.clone() };
+    let schedule_result = schedule::validate(&without_restart);
+    let schedule_ok = schedule_result.is_ok();
+    check("schedule", schedule_result);
+    if schedule_ok && config.cron_restart.is_some() {
+        check("cron_restart", schedule::validate(config));
+    }
+    // 只检查用户和组是否存在，是否有权限切换由守护进程在启动时检查
+    check("user", user::lookup(config).map(|_| ()));
+    if let Some(rlimits) = &config.rlimits {
+        check("rlimits", rlimit::parse(rlimits).map(|_| ()));
+    }
+    #[cfg(target_os = "linux")]
+    check("cgroup", cgroup::validate(config));
+    if let Some(max_memory) = &config.max_memory {
+        check("max_memory", utils::parse_memory_size(max_memory).map(|_| ()));
+    }
+    check("max_cpu_duration", duration(&config.max_cpu_duration));
+    check("kill_timeout", duration(&config.kill_timeout));
+    if let Some(instances) = &config.instances {
+        check("instances", cluster::resolve_count(instances).map(|_| ()));
+    }
+    if let Some(health_check) = &config.health_check {
+        check("health_check", health::validate(health_check));
+    }
+    #[cfg(unix)]
+    for address in config.listen.iter().flatten() {
+        check("listen", sockets::validate(address));
+    }
+    check("watch", watch::validate(config));
+    if let Some(hooks) = &config.hooks {
+        check("hooks", hooks::validate(hooks));
+    }
+    errors
+}
 
-    // 保存运行中的进程
-    let mut running = RUNNING_PROCESSES.lock().unwrap();
-    running.insert(name.to_string(), child);
+// 进程的环境变量和运行身份，钩子命令使用同样的设置
+pub fn process_env(config: &ProcessConfig) -> Result<(HashMap<String, String>, Option<user::Credentials>)> {
+    let mut env = environment::build_env(config)
+        .with_context(|| format!("Failed to prepare environment for process {}", config.name))?;
 
-    println!("Process {} started with PID {}", name, pid);
-    Ok(())
+    let credentials = user::resolve(config)?;
+    if let Some(creds) = &credentials {
+        // 未显式配置时，HOME/USER/LOGNAME 指向目标用户
+        let explicit = config.env.as_ref();
+        let mut set_default = |key: &str, value: &Option<String>| {
+            if let Some(value) = value {
+                if !explicit.map(|e| e.contains_key(key)).unwrap_or(false) {
+                    env.insert(key.to_string(), value.clone());
+                }
+            }
+        };
+        set_default("HOME", &creds.home);
+        set_default("USER", &creds.user);
+        set_default("LOGNAME", &creds.user);
+    }
+    Ok((env, credentials))
 }
 
 // 停止进程
 pub fn stop_process(name: &str) -> Result<()> {
-    let mut running = RUNNING_PROCESSES.lock().unwrap();
-    
-    if let Some(mut child) = running.remove(name) {
-        // 尝试终止进程
-        match child.kill() {
-            Ok(_) => {
-                println!("Process {} stopped", name);
-            }
-            Err(e) => {
-                println!("Failed to stop process {}: {}", name, e);
+    let config = get_process(name);
+    if config.as_ref().filter(|c| cluster::is_parent(c)).is_some() {
+        return cluster::stop(name);
+    }
+
+    // 进程仍在运行时，终止前执行 pre_stop 钩子，失败不影响停止
+    let was_running = config.as_ref().and_then(|c| c.pid).map(is_alive).unwrap_or(false);
+    if let Some(config) = config.as_ref().filter(|_| was_running) {
+        if let Err(e) = hooks::run(config, Hook::PreStop) {
+            println!("{:#}", e);
+        }
+    }
+
+    let child = RUNNING_PROCESSES.lock().unwrap().remove(name);
+    let timeout = config.as_ref().map(kill_timeout).unwrap_or(DEFAULT_KILL_TIMEOUT);
+    schedule::stop_detached(name, timeout);
+
+    // 主进程退出后无法再通过 PID 找到 cgroup，需要提前记录
+    #[cfg(target_os = "linux")]
+    let cgroup_dir = config.as_ref().and_then(|c| c.pid).and_then(cgroup::of_pid);
+
+    match (child, &config) {
+        (Some(mut child), _) => {
+            // 尝试终止进程
+            match terminate(child.id(), Some(&mut child), timeout) {
+                Ok(_) => {
+                    println!("Process {} stopped", name);
+                }
+                Err(e) => {
+                    println!("Failed to stop process {}: {}", name, e);
+                }
             }
         }
-    } else {
-        // 检查进程是否在配置中
-        if let Some(config) = get_process(name) {
+        (None, Some(config)) => {
             if let Some(pid) = config.pid {
                 // 尝试通过系统API终止进程
-                let mut system = System::new_all();
-                system.refresh_all();
-                
-                if let Some(process) = system.process(sysinfo::Pid::from(pid as usize)) {
-                    process.kill();
-                    println!("Process {} with PID {} stopped", name, pid);
+                if is_alive(pid) {
+                    match terminate(pid, None, timeout) {
+                        Ok(_) => println!("Process {} with PID {} stopped", name, pid),
+                        Err(e) => println!("Failed to stop process {}: {}", name, e),
+                    }
                 } else {
                     println!("Process {} not found in system", name);
                 }
             } else {
                 println!("Process {} has no PID", name);
             }
-        } else {
+        }
+        (None, None) => {
             return Err(anyhow::anyhow!("Process {} not found", name));
         }
     }
 
+    // 通过 cgroup 终止残留的子进程
+    #[cfg(target_os = "linux")]
+    if let Some(dir) = cgroup_dir {
+        if let Err(e) = cgroup::kill(&dir) {
+            println!("Failed to kill cgroup of process {}: {}", name, e);
+        }
+        cgroup::remove(&dir);
+    }
+
     // 更新进程状态
     if let Some(config) = get_process(name) {
         let mut updated_config = config.clone();
@@ -172,85 +404,421 @@ pub fn stop_process(name: &str) -> Result<()> {
         update_process(updated_config)?;
     }
 
+    if let Some(config) = config.as_ref().filter(|_| was_running) {
+        let mut event = Event::new(EventKind::Stopped, name);
+        event.pid = config.pid;
+        events::emit(event);
+        if let Err(e) = hooks::run(config, Hook::PostStop) {
+            println!("{:#}", e);
+        }
+    }
+
     Ok(())
 }
 
+// 停止进程时等待的时间
+fn kill_timeout(config: &ProcessConfig) -> Duration {
+    config
+        .kill_timeout
+        .as_deref()
+        .and_then(|t| humantime::parse_duration(t).ok())
+        .unwrap_or(DEFAULT_KILL_TIMEOUT)
+}
+
+// 取出自己启动的子进程，由调用方负责回收
+pub fn take_child(name: &str) -> Option<Child> {
+    RUNNING_PROCESSES.lock().unwrap().remove(name)
+}
+
+// 检查进程是否存在（僵尸进程视为已退出）
+pub fn is_alive(pid: u32) -> bool {
+    let pid = sysinfo::Pid::from(pid as usize);
+    let mut system = System::new();
+    system.refresh_process(pid);
+    system
+        .process(pid)
+        .map(|p| p.status() != sysinfo::ProcessStatus::Zombie)
+        .unwrap_or(false)
+}
+
+// 先发送 SIGTERM，超时后强制终止；Windows 上直接终止
+pub fn terminate(pid: u32, child: Option<&mut Child>, timeout: Duration) -> Result<()> {
+    #[cfg(unix)]
+    {
+        if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let deadline = Instant::now() + timeout;
+        match child {
+            Some(child) => {
+                while Instant::now() < deadline {
+                    if child.try_wait()?.is_some() {
+                        return Ok(());
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                child.kill()?;
+                child.wait()?;
+            }
+            None => {
+                while Instant::now() < deadline {
+                    if !is_alive(pid) {
+                        return Ok(());
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGKILL);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = timeout;
+        match child {
+            Some(child) => {
+                child.kill()?;
+                child.wait()?;
+            }
+            None => {
+                let mut system = System::new();
+                let pid = sysinfo::Pid::from(pid as usize);
+                system.refresh_process(pid);
+                if let Some(process) = system.process(pid) {
+                    process.kill();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 // 重启进程
 pub fn restart_process(name: &str) -> Result<()> {
+    restart_process_with_reason(name, "manual restart")
+}
+
+// 重启进程，并在进程历史中记录原因
+pub fn restart_process_with_reason(name: &str, reason: &str) -> Result<()> {
+    if get_process(name).filter(cluster::is_parent).is_some() {
+        cluster::restart(name, reason)?;
+        println!("Process {} restarted ({})", name, reason);
+        events::emit(Event::new(EventKind::Restarted, name).message(reason));
+        return Ok(());
+    }
     if let Some(config) = get_process(name) {
         let cmd = config.command.clone();
         let working_dir = config.working_dir.clone();
-        let env = config.env.clone();
-        
-        // 将环境变量转换为Vec<String>格式
-        let env_vars = env.map(|map| {
-            map.iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<String>>()
-        });
         
         // 停止进程
         let _ = stop_process(name);
         
-        // 启动进程
+        // 启动进程，使用配置中的环境变量
         start_process(
             name,
             &cmd,
             working_dir.as_deref(),
-            env_vars.as_ref(),
+            None,
         )?;
+
+        if let Some(mut config) = get_process(name) {
+            config.restarts += 1;
+            config.record_history("restart", Some(reason.to_string()));
+            update_process(config)?;
+        }
         
-        println!("Process {} restarted", name);
+        println!("Process {} restarted ({})", name, reason);
+        events::emit(Event::new(EventKind::Restarted, name).message(reason));
         Ok(())
     } else {
         Err(anyhow::anyhow!("Process {} not found", name))
     }
 }
 
+// 平滑替换运行中的进程：先启动新进程并等待就绪，再停止旧进程；
+// 新进程启动失败或未能就绪时只终止新进程，旧进程继续运行。
+// 只有通过 listen 传入套接字的进程才能与旧进程同时运行，其他进程自己监听端口会冲突，
+// 就绪检查也会由旧进程应答，所以依次停止、启动并等待就绪；进程没有在运行时直接启动
+pub async fn replace_process(name: &str, reason: &str) -> Result<()> {
+    let config = get_process(name).ok_or_else(|| anyhow::anyhow!("Process {} not found", name))?;
+    let overlap = config.listen.as_ref().map(|listen| !listen.is_empty()).unwrap_or(false);
+    let old_pid = match config.pid.filter(|pid| overlap && is_alive(*pid)) {
+        Some(pid) => pid,
+        None => {
+            let (name, reason) = (name.to_string(), reason.to_string());
+            events::spawn_blocking(move || restart_process_with_reason(&name, &reason)).await??;
+            return health::wait_ready(&config.name).await;
+        }
+    };
+
+    let replacement = config.clone();
+    let mut child = events::spawn_blocking(move || -> Result<Child> {
+        if let Err(e) = hooks::run(&replacement, Hook::PreStart) {
+            if hooks::aborts_start(&replacement) {
+                return Err(e);
+            }
+            println!("{:#}; starting process {} anyway", e, replacement.name);
+        }
+        spawn(&replacement, true)
+    })
+    .await?
+    .with_context(|| format!("Process {} was not replaced, the old process (PID {}) is still running", name, old_pid))?;
+    let pid = child.id();
+    println!("Process {} started with PID {}, waiting for it to become ready", name, pid);
+
+    if let Err(e) = health::wait_pid_ready(name, pid).await {
+        let timeout = kill_timeout(&config);
+        events::spawn_blocking(move || terminate_group(pid, &mut child, timeout)).await??;
+        return Err(e.context(format!(
+            "Process {} was not replaced, the new process (PID {}) was stopped and the old process (PID {}) is still running",
+            name, pid, old_pid
+        )));
+    }
+
+    // 新进程已就绪，先记录新进程，监控线程不会把旧进程退出当作崩溃；
+    // 两者在同一个 cgroup 中，同时运行期间共用 memory_max、pids_max 等限制，只终止旧进程本身
+    let (name, reason) = (name.to_string(), reason.to_string());
+    events::spawn_blocking(move || -> Result<()> {
+        if let Err(e) = hooks::run(&config, Hook::PreStop) {
+            println!("{:#}", e);
+        }
+        let mut old_child = {
+            let mut running = RUNNING_PROCESSES.lock().unwrap();
+            running.insert(name.clone(), child)
+        };
+        if let Some(mut config) = get_process(&name) {
+            config.start_time = Some(SystemTime::now());
+            config.status = ProcessStatus::Running;
+            config.pid = Some(pid);
+            config.updated_at = Local::now().to_rfc3339();
+            config.restarts += 1;
+            config.record_history("restart", Some(reason.clone()));
+            update_process(config)?;
+        }
+
+        if let Err(e) = terminate(old_pid, old_child.as_mut(), kill_timeout(&config)) {
+            println!("Failed to stop process {} (PID {}): {}", name, old_pid, e);
+        }
+        events::emit(Event::new(EventKind::Stopped, &name).pid(old_pid));
+        if let Err(e) = hooks::run(&config, Hook::PostStop) {
+            println!("{:#}", e);
+        }
+
+        events::emit(Event::new(EventKind::Started, &name).pid(pid));
+        if let Some(config) = get_process(&name) {
+            if let Err(e) = hooks::run(&config, Hook::PostStart) {
+                println!("{:#}", e);
+            }
+        }
+        println!("Process {} replaced ({}), old PID {}, new PID {}", name, reason, old_pid, pid);
+        events::emit(Event::new(EventKind::Restarted, &name).message(reason));
+        Ok(())
+    })
+    .await?
+}
+
+// 终止以 pid 为组长的整个进程组，用于未能就绪的替换进程；它与旧进程共用 cgroup，不能终止整个 cgroup
+fn terminate_group(pid: u32, child: &mut Child, timeout: Duration) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let group = -(pid as libc::pid_t);
+        unsafe {
+            libc::kill(group, libc::SIGTERM);
+        }
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline && child.try_wait()?.is_none() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        // 组长退出后组内可能还有子进程
+        unsafe {
+            libc::kill(group, libc::SIGKILL);
+        }
+        child.wait()?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    terminate(pid, Some(child), timeout)
+}
+
+// 进程配置及其运行时信息，JSON/YAML 输出时字段名与 API 中的 ProcessConfig 一致
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessView {
+    #[serde(flatten)]
+    pub config: ProcessConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ResourceUsage>,
+    #[cfg(target_os = "linux")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup_path: Option<String>,
+    #[cfg(target_os = "linux")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<cgroup::Stats>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effective_rlimits: Vec<rlimit::EffectiveLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_run: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_restart: Option<String>,
+    // 集群实例的状态，只在查看详情时填充
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<InstanceState>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstanceState {
+    pub name: String,
+    pub status: ProcessStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+}
+
+// 刷新进程信息，CPU 使用率需要间隔两次采样才能计算
+fn sample_system(with_cpu: bool) -> System {
+    let mut system = System::new();
+    system.refresh_processes();
+    if with_cpu {
+        std::thread::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL);
+        system.refresh_processes();
+    }
+    system
+}
+
+// 收集进程的运行时信息，detailed 时包括 cgroup 和实际生效的资源限制
+#[cfg_attr(not(target_os = "linux"), allow(unused_mut, unused_variables))]
+fn process_view(config: ProcessConfig, system: &System, detailed: bool) -> ProcessView {
+    // 通过 API 获取的集群配置已经包含汇总的状态
+    let config = if config.running_instances.is_some() { config } else { cluster::with_summary(config) };
+    let process = config.pid.and_then(|pid| system.process(sysinfo::Pid::from(pid as usize)));
+    let mut view = ProcessView {
+        uptime_seconds: process.map(|p| p.run_time()),
+        usage: process.map(metrics::resource_usage),
+        #[cfg(target_os = "linux")]
+        cgroup_path: None,
+        #[cfg(target_os = "linux")]
+        cgroup: None,
+        effective_rlimits: Vec::new(),
+        next_run: schedule::next_run(&config, Local::now()).map(|t| t.to_rfc3339()),
+        next_restart: schedule::next_restart(&config, Local::now()).map(|t| t.to_rfc3339()),
+        members: Vec::new(),
+        config,
+    };
+    if detailed && cluster::is_parent(&view.config) {
+        view.members = cluster::instances_of(&view.config.name)
+            .into_iter()
+            .map(|instance| InstanceState {
+                name: instance.name,
+                status: instance.status,
+                pid: instance.pid,
+            })
+            .collect();
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(pid) = view.config.pid.filter(|_| detailed && process.is_some()) {
+        if let Some(dir) = cgroup::of_pid(pid) {
+            view.cgroup_path = Some(dir.display().to_string());
+            view.cgroup = Some(cgroup::stats(&dir));
+        }
+        view.effective_rlimits = rlimit::effective_limits(pid).unwrap_or_default();
+    }
+    view
+}
+
+// 以 JSON/YAML 输出单个进程，用于各个操作命令的结果
+pub fn print_process(config: ProcessConfig, output: OutputFormat) -> Result<()> {
+    output::print(output, &process_view(config, &sample_system(true), false))
+}
+
+// 单个进程的运行时信息，供 API 使用
+pub fn view(name: &str, detailed: bool) -> Result<ProcessView> {
+    let config = get_process(name).ok_or_else(|| anyhow::anyhow!("Process {} not found", name))?;
+    Ok(process_view(config, &sample_system(true), detailed))
+}
+
+// 所有进程的运行时信息，供 API 使用
+pub fn views() -> HashMap<String, ProcessView> {
+    let system = sample_system(true);
+    let processes = config::PROCESSES.read().unwrap().clone();
+    processes
+        .into_iter()
+        .map(|(name, config)| (name, process_view(config, &system, false)))
+        .collect()
+}
+
 // 列出所有进程
-pub fn list_processes() -> Result<()> {
+pub fn list_processes(output: OutputFormat) -> Result<()> {
+    let system = sample_system(output != OutputFormat::Table);
     let processes = config::PROCESSES.try_read().unwrap();
-    
-    if processes.is_empty() {
+
+    let mut update_configs: Vec<ProcessConfig> = vec![];
+    let mut configs = Vec::new();
+    for config in processes.values() {
+        let mut config = config.clone();
+        let new_status = match config.pid {
+            // 集群的状态由实例决定
+            _ if cluster::is_parent(&config) => config.status,
+            Some(pid) if system.process(sysinfo::Pid::from(pid as usize)).is_some() => ProcessStatus::Running,
+            Some(_) => ProcessStatus::Stopped,
+            // 检查无PID但状态显示运行的情况
+            None if config.status == ProcessStatus::Running => ProcessStatus::Stopped,
+            None => config.status,
+        };
+
+        // 如果状态不一致则更新配置文件
+        if config.status != new_status {
+            config.status = new_status;
+            config.updated_at = Local::now().to_rfc3339();
+            if new_status != ProcessStatus::Running {
+                config.pid = None;
+            }
+            update_configs.push(config.clone());
+        }
+        configs.push(config);
+    }
+    drop(processes);
+    let _ = config::update_processes(update_configs);
+    let views: Vec<ProcessView> = configs
+        .into_iter()
+        .map(|config| process_view(config, &system, false))
+        .collect();
+    print_list(views, output)
+}
+
+// 输出进程列表，进程信息可能来自本地或远程的 API
+pub fn print_list(mut views: Vec<ProcessView>, output: OutputFormat) -> Result<()> {
+    views.sort_by(|a, b| a.config.name.cmp(&b.config.name));
+
+    if !output.is_human() {
+        return output::print(output, &views);
+    }
+
+    if views.is_empty() {
         println!("No processes found");
         return Ok(());
     }
-    
-    println!("{:<20} {:<10} {:<10} {:<20} {:<10}", "NAME", "STATUS", "PID", "START TIME", "UPTIME");
-    println!("{:-<20} {:-<10} {:-<10} {:-<20} {:-<10}", "", "", "", "", "");
-    let mut update_configs:Vec<ProcessConfig> = vec![];
-    for (name, config) in processes.iter() {
-        let mut system = System::new_all();
-        system.refresh_all();
-        // println!("{:?}",config);
-        let (pid_str, status_str) = match config.pid {
-            Some(pid) => {
-                let is_running = system.process(sysinfo::Pid::from(pid as usize)).is_some();
-                let new_status = if is_running { ProcessStatus::Running } else { ProcessStatus::Stopped };
-                
-                // 如果状态不一致则更新配置文件
-                if config.status != new_status {
-                    let mut updated_config = config.clone();
-                    updated_config.status = new_status;
-                    updated_config.updated_at = Local::now().to_rfc3339();
-                    if !is_running { updated_config.pid = None; }
-                    update_configs.push(updated_config);
 
-                }
-                
-                (pid.to_string(), format!("{:?}", new_status))
-            },
-            None => {
-                // 检查无PID但状态显示运行的情况
-                if config.status == ProcessStatus::Running {
-                    let mut updated_config = config.clone();
-                    updated_config.status = ProcessStatus::Stopped;
-                    updated_config.updated_at = Local::now().to_rfc3339();
-                    update_configs.push(updated_config);
-                }
-                ("-".to_string(), format!("{:?}", config.status))
-            },
-        };
+    let wide = output == OutputFormat::Wide;
+    if wide {
+        println!(
+            "{:<20} {:<12} {:<10} {:<20} {:<14} {:<8} {:<12} {:<9} COMMAND",
+            "NAME", "STATUS", "PID", "START TIME", "UPTIME", "CPU", "MEMORY", "RESTARTS"
+        );
+        let widths = [20, 12, 10, 20, 14, 8, 12, 9, 10];
+        println!("{}", widths.map(|w| "-".repeat(w)).join(" "));
+    } else {
+        println!("{:<20} {:<12} {:<10} {:<20} {:<10}", "NAME", "STATUS", "PID", "START TIME", "UPTIME");
+        println!("{:-<20} {:-<12} {:-<10} {:-<20} {:-<10}", "", "", "", "", "");
+    }
+    for view in &views {
+        let config = &view.config;
+        let pid_str = config.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string());
         let start_time_str = match config.start_time {
             Some(time) => {
                 let time: chrono::DateTime<Local> = time.into();
@@ -258,202 +826,556 @@ pub fn list_processes() -> Result<()> {
             },
             None => "-".to_string(),
         };
-        
-        let uptime_str = match config.start_time {
-            Some(time) => {
-                let uptime = SystemTime::now().duration_since(time);
-                
-                if let Ok(uptime) = uptime {
-                    format!("{}s", uptime.as_secs())
-                } else {
-                    "-".to_string()
-                }
-            },
-            None => "-".to_string(),
+        let uptime_str = view.uptime_seconds.map(utils::format_uptime).unwrap_or_else(|| "-".to_string());
+        // 集群显示运行中的实例数
+        let status_str = match (config.running_instances, &config.instances) {
+            (Some(running), Some(instances)) => {
+                let total = cluster::resolve_count(instances).unwrap_or(0);
+                format!("{:?} {}/{}", config.status, running, total)
+            }
+            _ => format!("{:?}", config.status),
         };
-        
-        println!(
-            "{:<20} {:<10} {:<10} {:<20} {:<10}",
-            name,
-            status_str,
-            pid_str,
-            start_time_str,
-            uptime_str
-        );
+
+        if wide {
+            let cpu_str = view.usage.map(|u| format!("{:.1}%", u.cpu_percent)).unwrap_or_else(|| "-".to_string());
+            let memory_str = view
+                .usage
+                .map(|u| utils::format_memory_size(u.memory_bytes / 1024))
+                .unwrap_or_else(|| "-".to_string());
+            println!(
+                "{:<20} {:<12} {:<10} {:<20} {:<14} {:<8} {:<12} {:<9} {}",
+                config.name,
+                status_str,
+                pid_str,
+                start_time_str,
+                uptime_str,
+                cpu_str,
+                memory_str,
+                config.restarts,
+                config.command
+            );
+        } else {
+            println!(
+                "{:<20} {:<12} {:<10} {:<20} {:<10}",
+                config.name,
+                status_str,
+                pid_str,
+                start_time_str,
+                uptime_str
+            );
+        }
     }
-    // let mut p = PROCESSES.try_write().expect("Failed to write to the global processes lock");
-    drop(processes);
-    let _ = config::update_processes(update_configs);
     Ok(())
 }
 
 // 显示进程详情
-pub fn show_details(name: &str) -> Result<()> {
-    if let Some(config) = get_process(name) {
-        println!("Process: {}", config.name);
-        println!("Command: {}", config.command);
-        println!("Status: {:?}", config.status);
-        
-        if let Some(pid) = config.pid {
-            println!("PID: {}", pid);
-            
-            // 检查进程是否真的在运行
-            let mut system = System::new_all();
-            system.refresh_all();
-            
-            if let Some(process) = system.process(sysinfo::Pid::from(pid as usize)) {
-                println!("Memory usage: {} KB", process.memory() / 1024);
-                println!("CPU usage: {:.2}%", process.cpu_usage());
-                println!("Running time: {} seconds", process.run_time());
-            } else {
-                println!("Process not found in system (may have terminated)");
+pub fn show_details(name: &str, output: OutputFormat) -> Result<()> {
+    print_details(&view(name, true)?, output)
+}
+
+// 输出进程详情
+pub fn print_details(view: &ProcessView, output: OutputFormat) -> Result<()> {
+    if !output.is_human() {
+        return output::print(output, &view);
+    }
+
+    let config = &view.config;
+    println!("Process: {}", config.name);
+    println!("Command: {}", config.command);
+    println!("Status: {:?}", config.status);
+
+    if let (Some(instances), Some(running)) = (&config.instances, config.running_instances) {
+        println!("Instances: {}/{} running", running, cluster::resolve_count(instances).unwrap_or(0));
+        for instance in &view.members {
+            println!(
+                "  {:<20} {:<10} {}",
+                instance.name,
+                format!("{:?}", instance.status),
+                instance.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string())
+            );
+        }
+    } else if let Some(parent) = &config.instance_of {
+        println!("Instance of: {} (#{})", parent, config.instance_id.unwrap_or(0));
+    }
+    if let Some(port) = config.port {
+        println!("Port: {}", port as u32 + config.instance_id.unwrap_or(0));
+    }
+    if let Some(listen) = config.listen.as_ref().filter(|l| !l.is_empty()) {
+        println!("Listen: {}", listen.join(", "));
+    }
+    if config.is_job() {
+        println!("Type: {:?}", config.process_type);
+    }
+    if let Some(schedule) = &config.schedule {
+        let next = match (&view.next_run, config.status) {
+            (_, ProcessStatus::Stopped) => "paused".to_string(),
+            (Some(next), _) => next.clone(),
+            (None, _) => "-".to_string(),
+        };
+        println!("Schedule: {} (next run: {})", schedule, next);
+    }
+    if let (Some(cron_restart), Some(next)) = (&config.cron_restart, &view.next_restart) {
+        println!("Cron restart: {} (next: {})", cron_restart, next);
+    }
+    if !config.runs.is_empty() {
+        println!("Recent runs:");
+        for run in config.runs.iter().rev().take(5) {
+            println!(
+                "  {:<32} {:<8} {}",
+                run.started_at.as_deref().unwrap_or(&run.finished_at),
+                run.duration_seconds.map(|d| format!("{:.1}s", d)).unwrap_or_else(|| "-".to_string()),
+                run.status
+            );
+        }
+    }
+
+    if let Some(pid) = config.pid {
+        println!("PID: {}", pid);
+
+        // 检查进程是否真的在运行
+        if let Some(usage) = view.usage {
+            println!("Memory usage: {}", utils::format_memory_size(usage.memory_bytes / 1024));
+            println!("CPU usage: {:.2}%", usage.cpu_percent);
+            println!("Running time: {}", utils::format_uptime(view.uptime_seconds.unwrap_or(0)));
+
+            #[cfg(target_os = "linux")]
+            if let (Some(dir), Some(stats)) = (&view.cgroup_path, &view.cgroup) {
+                println!("Cgroup: {}", dir);
+                if let Some(current) = stats.memory_current {
+                    let limit = match stats.memory_max.as_deref() {
+                        Some("max") | None => "max".to_string(),
+                        Some(max) => max.parse::<u64>().map(|m| utils::format_memory_size(m / 1024)).unwrap_or_default(),
+                    };
+                    println!("  Memory: {} (limit {})", utils::format_memory_size(current / 1024), limit);
+                }
+                if let Some(peak) = stats.memory_peak {
+                    println!("  Memory peak: {}", utils::format_memory_size(peak / 1024));
+                }
+                if let Some(usage) = stats.cpu_usage_usec {
+                    println!("  CPU time: {:.2} seconds", usage as f64 / 1_000_000.0);
+                }
+                if let Some(pids) = stats.pids_current {
+                    println!("  Tasks: {}", pids);
+                }
+                if let (Some(oom), Some(oom_kill)) = (stats.oom, stats.oom_kill) {
+                    println!("  OOM events: {} (killed {})", oom, oom_kill);
+                }
+            }
+
+            if !view.effective_rlimits.is_empty() {
+                println!("Resource limits (soft / hard):");
+                for limit in &view.effective_rlimits {
+                    println!("  {:<12} {} / {}", limit.name, limit.soft, limit.hard);
+                }
             }
         } else {
-            println!("PID: -");
+            println!("Process not found in system (may have terminated)");
         }
-        
-        if let Some(dir) = &config.working_dir {
-            println!("Working directory: {}", dir);
+    } else {
+        println!("PID: -");
+        if let Some(rlimits) = &config.rlimits {
+            println!("Resource limits:");
+            let mut names: Vec<&String> = rlimits.keys().collect();
+            names.sort();
+            for name in names {
+                match &rlimits[name] {
+                    config::RlimitValue::Number(n) => println!("  {:<12} {}", name, n),
+                    config::RlimitValue::Text(s) => println!("  {:<12} {}", name, s),
+                }
+            }
         }
-        
-        if let Some(env) = &config.env {
-            println!("Environment variables:");
-            for (key, value) in env {
-                println!("  {}={}", key, value);
+    }
+
+    if let Some(dir) = &config.working_dir {
+        println!("Working directory: {}", dir);
+    }
+
+    if config.user.is_some() || config.group.is_some() {
+        println!(
+            "Run as: {}{}",
+            config.user.as_deref().unwrap_or("-"),
+            config.group.as_ref().map(|g| format!(":{}", g)).unwrap_or_default()
+        );
+    }
+    if let Some(groups) = &config.groups {
+        println!("Supplementary groups: {}", groups.join(", "));
+    }
+    
+    if let Some(env) = &config.env {
+        println!("Environment variables:");
+        for (key, value) in env {
+            println!("  {}={}", key, value);
+        }
+    }
+
+    if let Some(files) = &config.env_file {
+        println!("Environment files: {}", files.join(", "));
+    }
+
+    if config.inherit_env == Some(false) {
+        match &config.env_allowlist {
+            Some(allowlist) if !allowlist.is_empty() => {
+                println!("Inherit environment: only {}", allowlist.join(", "))
             }
+            _ => println!("Inherit environment: no"),
         }
-        
-        println!("Created at: {}", config.created_at);
-        println!("Updated at: {}", config.updated_at);
-        
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!("Process {} not found", name))
+    }
+    
+    if let Some(max_memory) = &config.max_memory {
+        println!("Max memory: {}", max_memory);
+    }
+    if let Some(max_cpu) = config.max_cpu_percent {
+        println!(
+            "Max CPU: {}% for {}",
+            max_cpu,
+            config.max_cpu_duration.as_deref().unwrap_or("60s")
+        );
+    }
+
+    println!("Restarts: {}", config.restarts);
+    if !config.history.is_empty() {
+        println!("Recent history:");
+        for entry in config.history.iter().rev().take(10) {
+            match &entry.reason {
+                Some(reason) => println!("  {} {} ({})", entry.time, entry.event, reason),
+                None => println!("  {} {}", entry.time, entry.event),
+            }
+        }
+    }
+    
+    println!("Created at: {}", config.created_at);
+    println!("Updated at: {}", config.updated_at);
+    
+    Ok(())
+}
+
+const DEFAULT_LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+// 日志文件超过 log_max_size 时复制为 <日志>.1 后清空原文件。
+// 进程以追加方式写入，清空后继续写在文件开头，不需要重新打开文件
+fn rotate_log(path: &Path) -> Result<()> {
+    let max_size = match &config::SERVER_CONF.read().unwrap().log_max_size {
+        Some(size) => utils::parse_memory_size(size)?,
+        None => DEFAULT_LOG_MAX_SIZE,
+    };
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return Ok(()),
+    };
+    if max_size == 0 || len <= max_size {
+        return Ok(());
+    }
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    fs::copy(path, &rotated).with_context(|| format!("Failed to rotate log file {}", path.display()))?;
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_len(0))
+        .with_context(|| format!("Failed to truncate log file {}", path.display()))?;
+    Ok(())
+}
+
+// 轮转运行中进程的日志文件
+fn rotate_logs() {
+    let paths: HashSet<PathBuf> = config::PROCESSES
+        .read()
+        .unwrap()
+        .values()
+        .filter(|config| config.status == ProcessStatus::Running)
+        .map(config::log_path)
+        .collect();
+    for path in paths {
+        if let Err(e) = rotate_log(&path) {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+}
+
+// 读取进程日志的最后若干行
+pub fn tail_log(name: &str, lines: usize) -> Result<String> {
+    let config = get_process(name).ok_or_else(|| anyhow::anyhow!("Process {} not found", name))?;
+    let path = config::log_path(&config);
+    let mut file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(String::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open log file {}", path.display())),
+    };
+
+    // 从文件末尾向前读取，直到包含足够的行
+    let len = file.metadata()?.len();
+    let mut chunk: u64 = 64 * 1024;
+    loop {
+        let start = len.saturating_sub(chunk);
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        let content = String::from_utf8_lossy(&buf);
+        let count = content.lines().count();
+        if start == 0 || count > lines {
+            let skip = count.saturating_sub(lines);
+            let mut result = content.lines().skip(skip).collect::<Vec<_>>().join("\n");
+            if !result.is_empty() {
+                result.push('\n');
+            }
+            return Ok(result);
+        }
+        chunk *= 4;
     }
 }
 
 // 移除进程
 pub fn remove_process(name: &str, force: bool) -> Result<()> {
+    // 配置无法写入时不停止进程
+    config::ensure_writable()?;
+    if get_process(name).filter(cluster::is_parent).is_some() {
+        cluster::remove(name, force)?;
+        events::emit(Event::new(EventKind::Removed, name));
+        return Ok(());
+    }
     if !force {
         // 停止进程
         let _ = stop_process(name);
     }
     
-    // 从配置中移除
+    // 从配置中移除，集群实例共用集群的套接字，由集群删除时关闭
+    let config = get_process(name);
     config::remove_process(name)?;
+    #[cfg(unix)]
+    if config.map(|c| c.instance_of.is_none()).unwrap_or(false) {
+        sockets::close(name);
+    }
     
     // 从运行中的进程列表中移除
     let mut running = RUNNING_PROCESSES.lock().unwrap();
     running.remove(name);
+    events::emit(Event::new(EventKind::Removed, name));
     
     Ok(())
 }
 
 // 显示进程状态
-pub fn show_status(name: Option<&str>) -> Result<()> {
-    if let Some(name) = name {
-        if let Some(config) = get_process(name) {
-            println!("Process: {}", config.name);
-            println!("Command: {}", config.command);
-            println!("Status: {:?}", config.status);
-            
-            if let Some(start_time) = config.start_time {
-                let start_time: chrono::DateTime<Local> = start_time.into();
-                let uptime = Local::now() - start_time;
-                println!("Started at: {}", start_time.format("%Y-%m-%d %H:%M:%S"));
-                println!("Uptime: {} seconds", uptime.num_seconds());
-            }
-            
-            if let Some(pid) = config.pid {
-                println!("PID: {}", pid);
-                
-                // 检查进程是否真的在运行
-                let mut system = System::new_all();
-                system.refresh_all();
-                
-                if let Some(process) = system.process(sysinfo::Pid::from(pid as usize)) {
-                    println!("Memory usage: {} KB", process.memory() / 1024);
-                    println!("CPU usage: {:.2}%", process.cpu_usage());
-                    println!("Running time: {} seconds", process.run_time());
-                } else {
-                    println!("Process not found in system (may have terminated)");
-                }
-            } else {
-                println!("PID: -");
-            }
-            
-            if let Some(dir) = &config.working_dir {
-                println!("Working directory: {}", dir);
-            }
-            
-            if let Some(env) = &config.env {
-                println!("Environment variables:");
-                for (key, value) in env {
-                    println!("  {}={}", key, value);
-                }
-            }
-            
-            println!("Created at: {}", config.created_at);
-            println!("Updated at: {}", config.updated_at);
+pub fn show_status(name: Option<&str>, output: OutputFormat) -> Result<()> {
+    match name {
+        Some(name) => print_status(&view(name, false)?, output),
+        // 显示所有进程状态
+        None => list_processes(output),
+    }
+}
+
+// 输出单个进程的状态
+pub fn print_status(view: &ProcessView, output: OutputFormat) -> Result<()> {
+    if !output.is_human() {
+        return output::print(output, view);
+    }
+
+    let config = &view.config;
+    println!("Process: {}", config.name);
+    println!("Command: {}", config.command);
+    println!("Status: {:?}", config.status);
+    
+    if let Some(start_time) = config.start_time {
+        let start_time: chrono::DateTime<Local> = start_time.into();
+        let uptime = Local::now() - start_time;
+        println!("Started at: {}", start_time.format("%Y-%m-%d %H:%M:%S"));
+        println!("Uptime: {}", utils::format_uptime(uptime.num_seconds().max(0) as u64));
+    }
+    
+    if let Some(pid) = config.pid {
+        println!("PID: {}", pid);
+        
+        // 检查进程是否真的在运行
+        if let Some(usage) = view.usage {
+            println!("Memory usage: {}", utils::format_memory_size(usage.memory_bytes / 1024));
+            println!("CPU usage: {:.2}%", usage.cpu_percent);
+            println!("Running time: {}", utils::format_uptime(view.uptime_seconds.unwrap_or(0)));
         } else {
-            return Err(anyhow::anyhow!("Process {} not found", name));
+            println!("Process not found in system (may have terminated)");
         }
     } else {
-        // 显示所有进程状态
-        list_processes()?;
+        println!("PID: -");
     }
     
+    if let Some(dir) = &config.working_dir {
+        println!("Working directory: {}", dir);
+    }
+    
+    if let Some(env) = &config.env {
+        println!("Environment variables:");
+        for (key, value) in env {
+            println!("  {}={}", key, value);
+        }
+    }
+    
+    println!("Created at: {}", config.created_at);
+    println!("Updated at: {}", config.updated_at);
     Ok(())
 }
 
 // 检查进程状态
 pub fn check_processes() -> Result<()> {
-    let mut system = System::new_all();
-    system.refresh_all();
+    let mut system = MONITOR_SYSTEM.lock().unwrap();
+    system.refresh_processes();
     
     let mut processes = crate::config::PROCESSES.write().unwrap();
     let mut running = RUNNING_PROCESSES.lock().unwrap();
+    let mut cpu_since = CPU_OVER_LIMIT_SINCE.lock().unwrap();
+
+    let mut changed = false;
+    let mut to_restart: Vec<(String, String)> = Vec::new();
     
     for (name, config) in processes.iter_mut() {
-        if config.status == ProcessStatus::Running {
-            if let Some(pid) = config.pid {
-                // 检查进程是否在系统中运行
-                if system.process(sysinfo::Pid::from(pid as usize)).is_none() {
-                    // 进程不在运行
-                    config.status = ProcessStatus::Failed;
-                    config.pid = None;
-                    config.updated_at = Local::now().to_rfc3339();
-                    
-                    // 从运行中的进程列表中移除
-                    running.remove(name);
-                    
-                    println!("Process {} (PID {}) is not running", name, pid);
-                    
-                    // 如果配置了自动重启，则标记需要重启
-                    let needs_restart = config.auto_restart;
-                    let process_name = name.clone();
-                    
-                    // 如果配置了自动重启，则在释放锁后重启进程
-                    if needs_restart {
-                        // 在这里不直接调用restart_process，而是克隆名称后在外部处理
-                        drop(processes);
-                        drop(running);
-                        let _ = restart_process(&process_name);
-                        return Ok(());
-                    }
+        if config.status != ProcessStatus::Running {
+            cpu_since.remove(name);
+            continue;
+        }
+        let pid = match config.pid {
+            Some(pid) => pid,
+            None => continue,
+        };
+
+        // 自己启动的子进程通过 try_wait 回收，避免僵尸进程被当作仍在运行
+        let exited = match running.get_mut(name) {
+            Some(child) if child.id() == pid => match child.try_wait() {
+                Ok(Some(status)) => Some((status.to_string(), status.code())),
+                Ok(None) => None,
+                Err(e) => Some((e.to_string(), None)),
+            },
+            _ => system
+                .process(sysinfo::Pid::from(pid as usize))
+                .is_none()
+                .then(|| ("not running".to_string(), None)),
+        };
+
+        if let Some((exit_status, exit_code)) = exited {
+            // oneshot 和 cron 成功退出不算失败
+            let completed = config.is_job() && exit_code == Some(0);
+            if config.is_job() {
+                config.record_run(config.start_time, &exit_status, exit_code);
+            }
+            config.status = if completed { ProcessStatus::Completed } else { ProcessStatus::Failed };
+            config.pid = None;
+            if !completed {
+                config.crashes += 1;
+            }
+            config.updated_at = Local::now().to_rfc3339();
+            changed = true;
+            
+            // 从运行中的进程列表中移除
+            running.remove(name);
+            cpu_since.remove(name);
+            
+            if completed {
+                println!("Process {} (PID {}) completed", name, pid);
+            } else {
+                println!("Process {} (PID {}) is not running: {}", name, pid, exit_status);
+            }
+            events::emit(
+                Event::new(EventKind::Exited, name)
+                    .pid(pid)
+                    .exit_code(exit_code)
+                    .message(exit_status.clone()),
+            );
+
+            // 检查是否被 OOM killer 终止，并清理 cgroup
+            #[cfg(target_os = "linux")]
+            if let Some(dir) = cgroup::path_for(name).filter(|d| d.exists()) {
+                if let Some(oom_kill) = cgroup::stats(&dir).oom_kill.filter(|n| *n > 0) {
+                    println!("Process {} was killed by the OOM killer ({} OOM kills)", name, oom_kill);
                 }
+                cgroup::kill(&dir).ok();
+                cgroup::remove(&dir);
+            }
+            
+            // 如果配置了自动重启，则在释放锁后重启进程
+            if config.auto_restart && !completed {
+                to_restart.push((name.clone(), format!("process exited unexpectedly ({})", exit_status)));
             }
+            continue;
+        }
+
+        // 检查内存和 CPU 阈值
+        if let Some(process) = system.process(sysinfo::Pid::from(pid as usize)) {
+            if let Some(reason) = exceeded_limits(name, config, process, &mut cpu_since) {
+                events::emit(Event::new(EventKind::Unhealthy, name).pid(pid).message(reason.clone()));
+                to_restart.push((name.clone(), reason));
+            }
+        }
+    }
+
+    drop(cpu_since);
+    drop(running);
+    drop(processes);
+    drop(system);
+
+    if changed {
+        config::save_processes()?;
+    }
+
+    for (name, reason) in to_restart {
+        println!("Restarting process {}: {}", name, reason);
+        if let Err(e) = restart_process_with_reason(&name, &reason) {
+            println!("Failed to restart process {}: {:#}", name, e);
+            events::emit(Event::new(EventKind::Errored, &name).message(format!("{:#}", e)));
         }
     }
     
     Ok(())
 }
 
+// 检查进程是否超过 max_memory 或持续超过 max_cpu_percent，返回需要重启的原因
+fn exceeded_limits(
+    name: &str,
+    config: &ProcessConfig,
+    process: &sysinfo::Process,
+    cpu_since: &mut HashMap<String, Instant>,
+) -> Option<String> {
+    if let Some(max_memory) = &config.max_memory {
+        if let Ok(limit) = utils::parse_memory_size(max_memory) {
+            let memory = process.memory();
+            if memory > limit {
+                return Some(format!(
+                    "memory usage {} exceeded max_memory {}",
+                    utils::format_memory_size(memory / 1024),
+                    max_memory
+                ));
+            }
+        }
+    }
+
+    if let Some(max_cpu) = config.max_cpu_percent {
+        let cpu = process.cpu_usage();
+        if cpu > max_cpu {
+            let duration = config
+                .max_cpu_duration
+                .as_deref()
+                .and_then(|d| humantime::parse_duration(d).ok())
+                .unwrap_or(DEFAULT_MAX_CPU_DURATION);
+            let since = *cpu_since.entry(name.to_string()).or_insert_with(Instant::now);
+            if since.elapsed() >= duration {
+                cpu_since.remove(name);
+                return Some(format!(
+                    "CPU usage {:.1}% above max_cpu_percent {}% for {}",
+                    cpu,
+                    max_cpu,
+                    humantime::format_duration(duration)
+                ));
+            }
+        } else {
+            cpu_since.remove(name);
+        }
+    }
+
+    None
+}
+
 // 定期检查进程状态
 pub fn start_process_monitor() {
     std::thread::spawn(|| {
+        events::set_thread_actor("monitor");
         loop {
             let _ = check_processes();
+            rotate_logs();
             std::thread::sleep(std::time::Duration::from_secs(5));
         }
     });
@@ -0,0 +1,385 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::cluster;
+use crate::config::{self, ProcessConfig, ProcessStatus, ServerConf, CONFIG_PATH, PROCESSES, PROCESS_CONFIG_PATH, SERVER_CONF, SERVER_OVERRIDES};
+use crate::events;
+use crate::process;
+use crate::server;
+use crate::validate;
+
+// 修改后不需要重启进程就能生效的字段，由监控、调度和文件监视读取最新的配置
+const LIVE_FIELDS: &[&str] = &[
+    "auto_restart",
+    "max_memory",
+    "max_cpu_percent",
+    "max_cpu_duration",
+    "kill_timeout",
+    "health_check",
+    "hooks",
+    "schedule",
+    "allow_overlap",
+    "cron_restart",
+    "watch",
+    "ignore",
+    "watch_delay",
+];
+
+// zapm.yaml 中需要重启守护进程才能生效的配置项
+const DAEMON_FIELDS: &[&str] = &["cgroup"];
+
+// 重新加载配置的结果
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReloadReport {
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    // 修改了配置的进程及变化的字段
+    #[serde(default)]
+    pub updated: BTreeMap<String, Vec<String>>,
+    // 正在运行、需要重启才能应用新配置的进程
+    #[serde(default)]
+    pub restart_required: Vec<String>,
+    // zapm.yaml 中变化的配置项
+    #[serde(default)]
+    pub server: Vec<String>,
+    // 监听地址改变后的新地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen: Option<String>,
+    // 需要重启守护进程才能生效的配置项
+    #[serde(default)]
+    pub daemon_restart_required: Vec<String>,
+    // 删除失败、仍在运行的进程及原因
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub remove_failed: BTreeMap<String, String>,
+}
+
+impl ReloadReport {
+    pub fn print(&self) {
+        if self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty() && self.server.is_empty() && self.remove_failed.is_empty() {
+            println!("Configuration reloaded, nothing changed");
+            return;
+        }
+        println!("Configuration reloaded");
+        if !self.added.is_empty() {
+            println!("  Added: {}", self.added.join(", "));
+        }
+        if !self.removed.is_empty() {
+            println!("  Removed: {}", self.removed.join(", "));
+        }
+        for (name, fields) in &self.updated {
+            println!("  Updated {}: {}", name, fields.join(", "));
+        }
+        if !self.server.is_empty() {
+            println!("  Server settings: {}", self.server.join(", "));
+        }
+        if let Some(listen) = &self.listen {
+            println!("  Now listening on {}", listen);
+        }
+        if !self.restart_required.is_empty() {
+            println!(
+                "Restart to apply the new configuration: {} (zapm restart <name>, or zapm reload <name> for clusters)",
+                self.restart_required.join(", ")
+            );
+        }
+        if !self.daemon_restart_required.is_empty() {
+            println!("Restart the zapm daemon to apply: {}", self.daemon_restart_required.join(", "));
+        }
+        for (name, error) in &self.remove_failed {
+            println!("Failed to remove {}: {}", name, error);
+        }
+    }
+}
+
+// 读取并检查配置文件，有任何问题时不重新加载；同时返回读取的内容
+fn read<T: DeserializeOwned + Default>(path: &Path) -> Result<(T, String)> {
+    let problems = validate::check_file(path)?;
+    if !problems.is_empty() {
+        let problems: Vec<String> = problems.iter().map(|problem| problem.to_string()).collect();
+        return Err(anyhow::anyhow!(
+            "Configuration not reloaded, {} has problems:\n{}",
+            path.display(),
+            problems.join("\n")
+        ));
+    }
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if content.trim().is_empty() {
+        return Ok((T::default(), content));
+    }
+    let value = serde_yaml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok((value, content))
+}
+
+// zapm.yaml 中变化的顶层配置项
+fn changed_settings(old: &ServerConf, new: &ServerConf) -> Vec<String> {
+    let (old, new) = match (serde_json::to_value(old), serde_json::to_value(new)) {
+        (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) => (old, new),
+        _ => return Vec::new(),
+    };
+    let mut changed: Vec<String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    changed.sort_unstable();
+    changed.dedup();
+    changed
+}
+
+// 保留进程的运行状态和统计，只采用文件中的配置
+fn keep_runtime(config: &mut ProcessConfig, live: &ProcessConfig) {
+    config.start_time = live.start_time;
+    config.status = live.status;
+    config.pid = live.pid;
+    config.running_instances = None;
+    config.created_at = live.created_at.clone();
+    config.updated_at = live.updated_at.clone();
+    config.restarts = live.restarts;
+    config.crashes = live.crashes;
+    config.history = live.history.clone();
+    config.runs = live.runs.clone();
+}
+
+// 文件中新增的进程以停止状态加入
+fn reset_runtime(config: &mut ProcessConfig, now: &str) {
+    config.start_time = None;
+    config.status = ProcessStatus::Stopped;
+    config.pid = None;
+    config.running_instances = None;
+    if config.created_at.is_empty() {
+        config.created_at = now.to_string();
+    }
+    config.updated_at = now.to_string();
+}
+
+// 修改了这些字段的进程需要重启才能应用新配置，集群的修改需要 zapm reload
+fn needs_restart(config: &ProcessConfig, fields: &[String]) -> bool {
+    cluster::is_parent(config) || fields.iter().any(|field| !LIVE_FIELDS.contains(&field.as_str()))
+}
+
+// 文件中已经不存在、需要删除的进程，按名称排序。
+// 集群的实例由 zapm 维护，文件中没有时加入 processes 保留；集群被删除时由集群一起删除，不单独列出
+fn plan_removals(live: &HashMap<String, ProcessConfig>, processes: &mut HashMap<String, ProcessConfig>) -> Vec<String> {
+    let mut removed = Vec::new();
+    for (name, config) in live {
+        if processes.contains_key(name) {
+            continue;
+        }
+        match &config.instance_of {
+            Some(parent) if processes.contains_key(parent) => {
+                processes.insert(name.clone(), config.clone());
+            }
+            Some(parent) if live.contains_key(parent) => {}
+            _ => removed.push(name.clone()),
+        }
+    }
+    removed.sort();
+    removed
+}
+
+// 使运行中的进程与 processes.yaml 一致，content 为读取的文件内容，之后以它为准，不会改写文件
+async fn apply_processes(mut processes: HashMap<String, ProcessConfig>, content: String, report: &mut ReloadReport) -> Result<()> {
+    let live = PROCESSES.read().unwrap().clone();
+    // 正在运行的进程，集群的任一实例在运行即视为集群在运行
+    let running: HashSet<&str> = live
+        .values()
+        .filter(|config| config.status == ProcessStatus::Running)
+        .map(|config| config.group_name())
+        .collect();
+
+    let removed = plan_removals(&live, &mut processes);
+
+    let now = Local::now().to_rfc3339();
+    let mut updates = Vec::new();
+    {
+        let mut current = PROCESSES.write().unwrap();
+        for (name, mut config) in processes {
+            let previous = current.get(&name).cloned();
+            match &previous {
+                Some(previous) => {
+                    let fields = config::changed_fields(previous, &config);
+                    keep_runtime(&mut config, previous);
+                    if !fields.is_empty() {
+                        config.updated_at = now.clone();
+                        if needs_restart(&config, &fields) && running.contains(name.as_str()) {
+                            report.restart_required.push(name.clone());
+                        }
+                        report.updated.insert(name.clone(), fields);
+                    }
+                }
+                None => {
+                    reset_runtime(&mut config, &now);
+                    report.added.push(name.clone());
+                }
+            }
+            updates.push((previous, config.clone()));
+            current.insert(name, config);
+        }
+    }
+    report.added.sort();
+    report.restart_required.sort();
+    for (previous, config) in updates {
+        events::record_update(previous.as_ref(), &config);
+    }
+
+    // 停止并删除文件中已经不存在的进程，processes.yaml 已经是新的内容，只保存运行状态；
+    // 某个进程删除失败时继续删除其他进程，最后一起报告
+    let names = removed.clone();
+    report.remove_failed = events::spawn_blocking(move || {
+        config::state_only(|| {
+            names
+                .iter()
+                .filter_map(|name| process::remove_process(name, false).err().map(|e| (name.clone(), format!("{:#}", e))))
+                .collect()
+        })
+    })
+    .await?;
+    report.removed = removed
+        .into_iter()
+        .filter(|name| !report.remove_failed.contains_key(name))
+        .collect();
+
+    config::mark_synced(content)?;
+    config::save_processes()
+}
+
+// 重新读取 processes.yaml 和 zapm.yaml 并应用到运行中的守护进程
+pub async fn reload() -> Result<ReloadReport> {
+    let (processes, content): (HashMap<String, ProcessConfig>, String) = read(PROCESS_CONFIG_PATH.as_path())?;
+    let (mut server_conf, _): (ServerConf, String) = read(&CONFIG_PATH.join("zapm.yaml"))?;
+    SERVER_OVERRIDES.read().unwrap().apply(&mut server_conf);
+    server_conf.api_base_url = format!("http://{}:{}", server_conf.host, server_conf.port);
+
+    let mut report = ReloadReport::default();
+    let previous = SERVER_CONF.read().unwrap().clone();
+    report.server = changed_settings(&previous, &server_conf);
+    report.daemon_restart_required = report
+        .server
+        .iter()
+        .filter(|key| DAEMON_FIELDS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+
+    // 先绑定新地址，失败时不应用任何修改
+    let listener = if previous.host != server_conf.host || previous.port != server_conf.port {
+        let listener = server::bind(&server_conf.host, server_conf.port).await?;
+        report.listen = Some(format!("http://{}:{}", server_conf.host, server_conf.port));
+        Some(listener)
+    } else {
+        None
+    };
+
+    apply_processes(processes, content, &mut report).await?;
+    *SERVER_CONF.write().unwrap() = server_conf;
+    if let Some(listener) = listener {
+        server::rebind(listener)?;
+        if let Some(listen) = &report.listen {
+            println!("Server listening on {}", listen);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InstancesValue;
+
+    fn process(name: &str, command: &str) -> ProcessConfig {
+        ProcessConfig {
+            name: name.to_string(),
+            command: command.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn instance(parent: &str, id: u32) -> ProcessConfig {
+        ProcessConfig {
+            instance_of: Some(parent.to_string()),
+            instance_id: Some(id),
+            ..process(&cluster::instance_name(parent, id), "./api")
+        }
+    }
+
+    fn cluster(name: &str) -> ProcessConfig {
+        ProcessConfig {
+            instances: Some(InstancesValue::Number(2)),
+            ..process(name, "./api")
+        }
+    }
+
+    fn map(configs: Vec<ProcessConfig>) -> HashMap<String, ProcessConfig> {
+        configs.into_iter().map(|config| (config.name.clone(), config)).collect()
+    }
+
+    #[test]
+    fn lists_changed_settings() {
+        let old = ServerConf::default();
+        assert!(changed_settings(&old, &old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.port = 2500;
+        new.events_max = Some(100);
+        // api_base_url 不序列化，不算修改
+        new.api_base_url = "http://localhost:2500".to_string();
+        assert_eq!(changed_settings(&old, &new), ["events_max", "port"]);
+        // 删除的配置项也算修改
+        assert_eq!(changed_settings(&new, &old), ["events_max", "port"]);
+    }
+
+    #[test]
+    fn restarts_only_for_settings_read_at_start() {
+        let config = process("api", "./api");
+        let fields = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert!(!needs_restart(&config, &fields(&["auto_restart", "health_check", "max_memory", "watch"])));
+        assert!(needs_restart(&config, &fields(&["command"])));
+        assert!(needs_restart(&config, &fields(&["auto_restart", "env"])));
+        // 集群的任何修改都需要 zapm reload
+        assert!(needs_restart(&cluster("web"), &fields(&["auto_restart"])));
+    }
+
+    #[test]
+    fn keeps_cluster_instances_not_in_the_file() {
+        let live = map(vec![process("api", "./api"), process("old", "./old"), cluster("web"), instance("web", 0), instance("web", 1)]);
+        let mut processes = map(vec![process("api", "./api"), cluster("web")]);
+        assert_eq!(plan_removals(&live, &mut processes), ["old"]);
+        let mut names: Vec<&String> = processes.keys().collect();
+        names.sort();
+        assert_eq!(names, ["api", "web", "web-0", "web-1"]);
+    }
+
+    #[test]
+    fn removes_instances_with_their_cluster() {
+        let live = map(vec![cluster("web"), instance("web", 0), instance("gone", 0)]);
+        let mut processes = map(vec![]);
+        // web 的实例由 web 一起删除，集群已不存在的实例单独删除
+        assert_eq!(plan_removals(&live, &mut processes), ["gone-0", "web"]);
+        assert!(processes.is_empty());
+    }
+
+    #[test]
+    fn keeps_runtime_state_of_updated_processes() {
+        let mut live = process("api", "./api");
+        live.status = ProcessStatus::Running;
+        live.pid = Some(42);
+        live.restarts = 3;
+        live.created_at = "2024-01-01T00:00:00+00:00".to_string();
+        let mut config = process("api", "./api --v2");
+        keep_runtime(&mut config, &live);
+        assert_eq!((config.status, config.pid, config.restarts), (ProcessStatus::Running, Some(42), 3));
+        assert_eq!(config.created_at, live.created_at);
+        assert_eq!(config.command, "./api --v2");
+
+        let mut added = process("worker", "./worker");
+        reset_runtime(&mut added, "2024-02-01T00:00:00+00:00");
+        assert_eq!((added.status, added.pid), (ProcessStatus::Stopped, None));
+        assert_eq!(added.created_at, "2024-02-01T00:00:00+00:00");
+    }
+}
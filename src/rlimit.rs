@@ -0,0 +1,182 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::config::RlimitValue;
+
+// 资源限制（仅 Linux）
+#[derive(Debug, Clone, Copy)]
+pub struct Limit {
+    #[cfg(target_os = "linux")]
+    resource: Resource,
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(target_os = "linux", not(target_env = "gnu")))]
+type Resource = libc::c_int;
+
+// 配置名称与 /proc/<pid>/limits 中的名称
+#[cfg(target_os = "linux")]
+const RESOURCES: &[(&str, Resource, &str)] = &[
+    ("as", libc::RLIMIT_AS, "Max address space"),
+    ("core", libc::RLIMIT_CORE, "Max core file size"),
+    ("cpu", libc::RLIMIT_CPU, "Max cpu time"),
+    ("data", libc::RLIMIT_DATA, "Max data size"),
+    ("fsize", libc::RLIMIT_FSIZE, "Max file size"),
+    ("locks", libc::RLIMIT_LOCKS, "Max file locks"),
+    ("memlock", libc::RLIMIT_MEMLOCK, "Max locked memory"),
+    ("msgqueue", libc::RLIMIT_MSGQUEUE, "Max msgqueue size"),
+    ("nice", libc::RLIMIT_NICE, "Max nice priority"),
+    ("nofile", libc::RLIMIT_NOFILE, "Max open files"),
+    ("nproc", libc::RLIMIT_NPROC, "Max processes"),
+    ("rtprio", libc::RLIMIT_RTPRIO, "Max realtime priority"),
+    ("rttime", libc::RLIMIT_RTTIME, "Max realtime timeout"),
+    ("sigpending", libc::RLIMIT_SIGPENDING, "Max pending signals"),
+    ("stack", libc::RLIMIT_STACK, "Max stack size"),
+];
+
+// 解析 rlimits 配置，值可以是数字、"unlimited" 或 "soft:hard"
+#[cfg(target_os = "linux")]
+pub fn parse(rlimits: &HashMap<String, RlimitValue>) -> Result<Vec<Limit>> {
+    let mut limits = Vec::new();
+    for (name, value) in rlimits {
+        let resource = RESOURCES
+            .iter()
+            .find(|(n, _, _)| *n == name.as_str())
+            .map(|(_, r, _)| *r)
+            .ok_or_else(|| anyhow::anyhow!("Unknown rlimit '{}'", name))?;
+        let (soft, hard) = parse_value(name, value)?;
+        limits.push(Limit { resource, soft, hard });
+    }
+    Ok(limits)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn parse(rlimits: &HashMap<String, RlimitValue>) -> Result<Vec<Limit>> {
+    if rlimits.is_empty() {
+        return Ok(Vec::new());
+    }
+    Err(anyhow::anyhow!("rlimits are only supported on Linux"))
+}
+
+// None 表示 unlimited
+fn parse_value(name: &str, value: &RlimitValue) -> Result<(Option<u64>, Option<u64>)> {
+    fn parse_one(name: &str, s: &str) -> Result<Option<u64>> {
+        match s.trim() {
+            "unlimited" | "infinity" => Ok(None),
+            s => s
+                .parse::<u64>()
+                .map(Some)
+                .map_err(|_| anyhow::anyhow!("Invalid value '{}' for rlimit '{}'", s, name)),
+        }
+    }
+
+    let (soft, hard) = match value {
+        RlimitValue::Number(n) => (Some(*n), Some(*n)),
+        RlimitValue::Text(s) => match s.split_once(':') {
+            Some((soft, hard)) => (parse_one(name, soft)?, parse_one(name, hard)?),
+            None => {
+                let v = parse_one(name, s)?;
+                (v, v)
+            }
+        },
+    };
+
+    if let (Some(soft), Some(hard)) = (soft, hard) {
+        if soft > hard {
+            return Err(anyhow::anyhow!("Soft limit {} exceeds hard limit {} for rlimit '{}'", soft, hard, name));
+        }
+    }
+    if soft.is_none() && hard.is_some() {
+        return Err(anyhow::anyhow!("Soft limit exceeds hard limit for rlimit '{}'", name));
+    }
+    Ok((soft, hard))
+}
+
+// 在 fork 之后、exec 之前设置资源限制
+#[cfg(target_os = "linux")]
+pub fn apply(limits: &[Limit]) -> std::io::Result<()> {
+    for limit in limits {
+        let rlim = libc::rlimit {
+            rlim_cur: limit.soft.map(|v| v as libc::rlim_t).unwrap_or(libc::RLIM_INFINITY),
+            rlim_max: limit.hard.map(|v| v as libc::rlim_t).unwrap_or(libc::RLIM_INFINITY),
+        };
+        if unsafe { libc::setrlimit(limit.resource, &rlim) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// 运行中进程实际生效的资源限制
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EffectiveLimit {
+    pub name: String,
+    pub soft: String,
+    pub hard: String,
+}
+
+// 读取运行中进程实际生效的资源限制
+#[cfg(target_os = "linux")]
+pub fn effective_limits(pid: u32) -> Result<Vec<EffectiveLimit>> {
+    let content = std::fs::read_to_string(format!("/proc/{}/limits", pid))?;
+    let mut limits = Vec::new();
+    for line in content.lines().skip(1) {
+        if let Some((name, _, title)) = RESOURCES.iter().find(|(_, _, title)| line.starts_with(title)) {
+            let mut fields = line[title.len()..].split_whitespace();
+            let soft = fields.next().unwrap_or("-").to_string();
+            let hard = fields.next().unwrap_or("-").to_string();
+            limits.push(EffectiveLimit { name: name.to_string(), soft, hard });
+        }
+    }
+    limits.sort();
+    Ok(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let text = |s: &str| RlimitValue::Text(s.to_string());
+        let cases = [
+            (RlimitValue::Number(65536), (Some(65536), Some(65536))),
+            (RlimitValue::Number(0), (Some(0), Some(0))),
+            (text("1024"), (Some(1024), Some(1024))),
+            (text("unlimited"), (None, None)),
+            (text("infinity"), (None, None)),
+            (text("512:1024"), (Some(512), Some(1024))),
+            (text(" 512 : 1024 "), (Some(512), Some(1024))),
+            (text("1024:unlimited"), (Some(1024), None)),
+            (text("unlimited:unlimited"), (None, None)),
+            (text("18446744073709551615"), (Some(u64::MAX), Some(u64::MAX))),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_value("nofile", &value).unwrap(), expected, "value {:?}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for value in ["", "abc", "-1", "1.5", "1024:", "2048:1024", "unlimited:1024", "18446744073709551616", "1:2:3"] {
+            assert!(
+                parse_value("nofile", &RlimitValue::Text(value.to_string())).is_err(),
+                "value {:?}",
+                value
+            );
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn rejects_unknown_resources() {
+        let rlimits = HashMap::from([("nofiles".to_string(), RlimitValue::Number(1))]);
+        assert!(parse(&rlimits).is_err());
+        let rlimits = HashMap::from([("core".to_string(), RlimitValue::Number(0))]);
+        assert_eq!(parse(&rlimits).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,306 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use croner::Cron;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::process::Child;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::config::{get_process, update_process, ProcessConfig, ProcessStatus, ProcessType, PROCESSES};
+use crate::events::{self, Event, EventKind};
+use crate::process;
+
+// 调度检查间隔
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+// 允许重叠运行时，仍在运行的上一次运行，退出后记录结果
+struct Detached {
+    name: String,
+    child: Child,
+    started: Option<SystemTime>,
+}
+
+static DETACHED: Lazy<Mutex<Vec<Detached>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// 每个进程的 cron 表达式和下一次触发时间
+type Timers = HashMap<String, (String, DateTime<Local>)>;
+
+fn parse(schedule: &str) -> Result<Cron> {
+    Cron::new(schedule)
+        .with_seconds_optional()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid schedule '{}': {}", schedule, e))
+}
+
+// 校验进程类型与 schedule 是否匹配
+pub fn validate(config: &ProcessConfig) -> Result<()> {
+    match (config.process_type, &config.schedule) {
+        (ProcessType::Cron, None) => {
+            return Err(anyhow::anyhow!("Process {} is a cron job but has no schedule", config.name))
+        }
+        (ProcessType::Cron, Some(schedule)) => {
+            parse(schedule)?;
+        }
+        (_, Some(_)) => {
+            return Err(anyhow::anyhow!("Process {} has a schedule, set its type to cron", config.name))
+        }
+        _ => {}
+    }
+    if let Some(cron_restart) = &config.cron_restart {
+        if config.is_job() {
+            return Err(anyhow::anyhow!("Process {} is a {:?} job, cron_restart only applies to services", config.name, config.process_type));
+        }
+        parse(cron_restart)?;
+    }
+    if config.is_job() && config.instances.is_some() {
+        return Err(anyhow::anyhow!("Process {} is a {:?} job and cannot run in cluster mode", config.name, config.process_type));
+    }
+    Ok(())
+}
+
+fn next_occurrence(expr: &str, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    parse(expr).ok()?.find_next_occurrence(&after, false).ok()
+}
+
+// 下一次计划运行的时间
+pub fn next_run(config: &ProcessConfig, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    if config.process_type != ProcessType::Cron {
+        return None;
+    }
+    next_occurrence(config.schedule.as_deref()?, after)
+}
+
+// 下一次定时重启的时间
+pub fn next_restart(config: &ProcessConfig, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    if config.is_job() || config.instance_of.is_some() {
+        return None;
+    }
+    next_occurrence(config.cron_restart.as_deref()?, after)
+}
+
+// 判断是否到期并计算下一次触发时间；新增或修改的表达式从现在开始计算，不会立即触发
+fn due(timers: &mut Timers, name: &str, expr: &str, now: DateTime<Local>) -> bool {
+    let known = timers.get(name).filter(|(e, _)| e == expr).map(|(_, next)| *next);
+    if known.map(|next| next > now).unwrap_or(false) {
+        return false;
+    }
+    match next_occurrence(expr, now) {
+        Some(next) => timers.insert(name.to_string(), (expr.to_string(), next)),
+        None => timers.remove(name),
+    };
+    known.is_some()
+}
+
+// 上一次运行仍在运行且不允许重叠时跳过本次运行
+fn skips_overlap(job: &ProcessConfig, alive: bool) -> bool {
+    job.status == ProcessStatus::Running && alive && !job.allow_overlap.unwrap_or(false)
+}
+
+// 按计划启动一次运行
+fn run(job: &ProcessConfig) -> Result<()> {
+    if job.status == ProcessStatus::Running {
+        let alive = job.pid.map(process::is_alive).unwrap_or(false);
+        if skips_overlap(job, alive) {
+            println!("Skipping scheduled run of {}: previous run (PID {}) is still running", job.name, job.pid.unwrap_or(0));
+            if let Some(mut config) = get_process(&job.name) {
+                config.record_history("skip", Some("previous run still running".to_string()));
+                update_process(config)?;
+            }
+            return Ok(());
+        }
+
+        // 上一次运行交给后台回收，进程本身使用新的运行
+        if let Some(child) = process::take_child(&job.name) {
+            DETACHED.lock().unwrap().push(Detached {
+                name: job.name.clone(),
+                child,
+                started: job.start_time,
+            });
+        }
+        if let Some(mut config) = get_process(&job.name) {
+            config.status = ProcessStatus::Completed;
+            config.pid = None;
+            update_process(config)?;
+        }
+    }
+
+    process::start_process(&job.name, &job.command, job.working_dir.as_deref(), None)?;
+    if let Some(mut config) = get_process(&job.name) {
+        config.record_history("run", Some("scheduled".to_string()));
+        update_process(config)?;
+    }
+    Ok(())
+}
+
+// 回收已结束的重叠运行并记录结果
+fn reap_detached() {
+    let mut finished = Vec::new();
+    DETACHED.lock().unwrap().retain_mut(|run| match run.child.try_wait() {
+        Ok(None) => true,
+        Ok(Some(status)) => {
+            finished.push((run.name.clone(), run.started, status.to_string(), status.code()));
+            false
+        }
+        Err(e) => {
+            finished.push((run.name.clone(), run.started, e.to_string(), None));
+            false
+        }
+    });
+    for (name, started, status, code) in finished {
+        println!("Earlier run of {} finished: {}", name, status);
+        if let Some(mut config) = get_process(&name) {
+            config.record_run(started, &status, code);
+            let _ = update_process(config);
+        }
+    }
+}
+
+// 停止进程时同时终止仍在运行的重叠运行
+pub fn stop_detached(name: &str, timeout: Duration) {
+    let runs: Vec<Detached> = {
+        let mut detached = DETACHED.lock().unwrap();
+        let (runs, rest) = detached.drain(..).partition(|run| run.name == name);
+        *detached = rest;
+        runs
+    };
+    for mut run in runs {
+        if let Err(e) = process::terminate(run.child.id(), Some(&mut run.child), timeout) {
+            println!("Failed to stop earlier run of {}: {}", name, e);
+        }
+    }
+}
+
+// 检查到期的 cron 任务和定时重启
+fn tick(runs: &mut Timers, restarts: &mut Timers) {
+    reap_detached();
+
+    // 有任务在运行时每次都检查进程，使记录的运行时长更准确
+    let jobs_running = PROCESSES
+        .read()
+        .unwrap()
+        .values()
+        .any(|config| config.is_job() && config.status == ProcessStatus::Running);
+    if jobs_running {
+        let _ = process::check_processes();
+    }
+
+    let now = Local::now();
+    let configs: Vec<ProcessConfig> = PROCESSES
+        .read()
+        .unwrap()
+        .values()
+        .filter(|config| config.instance_of.is_none())
+        .cloned()
+        .collect();
+    runs.retain(|name, _| configs.iter().any(|c| &c.name == name && c.process_type == ProcessType::Cron));
+    restarts.retain(|name, _| configs.iter().any(|c| &c.name == name && !c.is_job()));
+
+    for config in configs {
+        if config.process_type == ProcessType::Cron {
+            let schedule = match &config.schedule {
+                Some(schedule) => schedule,
+                None => continue,
+            };
+            // 停止的任务暂停调度，直到再次 start
+            if due(runs, &config.name, schedule, now) && config.status != ProcessStatus::Stopped {
+                if let Err(e) = run(&config) {
+                    println!("Failed to run scheduled job {}: {:#}", config.name, e);
+                    events::emit(Event::new(EventKind::Errored, &config.name).message(format!("{:#}", e)));
+                }
+            }
+        } else if let Some(cron_restart) = config.cron_restart.as_deref().filter(|_| !config.is_job()) {
+            // 只重启正在运行的服务，计入重启次数但不算崩溃
+            if due(restarts, &config.name, cron_restart, now) && config.status == ProcessStatus::Running {
+                let reason = format!("scheduled restart ({})", cron_restart);
+                println!("Restarting process {}: {}", config.name, reason);
+                if let Err(e) = process::restart_process_with_reason(&config.name, &reason) {
+                    println!("Failed to restart process {}: {:#}", config.name, e);
+                    events::emit(Event::new(EventKind::Errored, &config.name).message(format!("{:#}", e)));
+                }
+            }
+        }
+    }
+}
+
+// 启动调度线程
+pub fn start_scheduler() {
+    std::thread::spawn(|| {
+        events::set_thread_actor("scheduler");
+        let mut runs = HashMap::new();
+        let mut restarts = HashMap::new();
+        loop {
+            tick(&mut runs, &mut restarts);
+            std::thread::sleep(TICK_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, min: u32, sec: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 1, 15, hour, min, sec).unwrap()
+    }
+
+    #[test]
+    fn registers_new_schedules_without_firing() {
+        let mut timers = Timers::new();
+        assert!(!due(&mut timers, "job", "0 */5 * * * *", at(12, 4, 59)));
+        assert_eq!(timers["job"].1, at(12, 5, 0));
+    }
+
+    #[test]
+    fn fires_on_the_tick() {
+        let mut timers = Timers::new();
+        due(&mut timers, "job", "0 */5 * * * *", at(12, 3, 0));
+        // 触发前一秒
+        assert!(!due(&mut timers, "job", "0 */5 * * * *", at(12, 4, 59)));
+        // 正好在触发时间，之后计算下一次
+        assert!(due(&mut timers, "job", "0 */5 * * * *", at(12, 5, 0)));
+        assert_eq!(timers["job"].1, at(12, 10, 0));
+        // 同一时间不会重复触发
+        assert!(!due(&mut timers, "job", "0 */5 * * * *", at(12, 5, 0)));
+        assert!(!due(&mut timers, "job", "0 */5 * * * *", at(12, 5, 1)));
+    }
+
+    #[test]
+    fn fires_once_after_a_late_tick() {
+        let mut timers = Timers::new();
+        due(&mut timers, "job", "0 */5 * * * *", at(12, 3, 0));
+        // 检查晚于触发时间时只触发一次，下一次从现在开始计算
+        assert!(due(&mut timers, "job", "0 */5 * * * *", at(12, 11, 1)));
+        assert_eq!(timers["job"].1, at(12, 15, 0));
+        assert!(!due(&mut timers, "job", "0 */5 * * * *", at(12, 11, 2)));
+    }
+
+    #[test]
+    fn restarts_timer_when_schedule_changes() {
+        let mut timers = Timers::new();
+        due(&mut timers, "job", "0 */5 * * * *", at(12, 3, 0));
+        // 修改后的表达式从现在开始计算，不会因为旧的触发时间已到而立即触发
+        assert!(!due(&mut timers, "job", "0 0 * * * *", at(12, 5, 0)));
+        assert_eq!(timers["job"].1, at(13, 0, 0));
+        assert!(due(&mut timers, "job", "0 0 * * * *", at(13, 0, 0)));
+    }
+
+    #[test]
+    fn skips_overlapping_runs_unless_allowed() {
+        let mut job = ProcessConfig {
+            name: "job".to_string(),
+            process_type: ProcessType::Cron,
+            status: ProcessStatus::Running,
+            ..Default::default()
+        };
+        assert!(skips_overlap(&job, true));
+        // 记录为运行中但进程已经退出
+        assert!(!skips_overlap(&job, false));
+        job.allow_overlap = Some(true);
+        assert!(!skips_overlap(&job, true));
+        job.allow_overlap = None;
+        job.status = ProcessStatus::Completed;
+        assert!(!skips_overlap(&job, true));
+    }
+}
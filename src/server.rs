@@ -1,21 +1,33 @@
-use crate::config::{get_process, update_process, ProcessConfig, ProcessStatus, PROCESSES};
+use crate::auth::{self, Identity};
+use crate::cluster;
+use crate::config::{get_process, update_process, HealthCheck, Hooks, InstancesValue, ProcessConfig, ProcessStatus, ProcessType, RlimitValue, Role, WatchValue, PROCESSES};
+use crate::events::{self, Event, EventKind};
+use crate::hub;
 use crate::process::{restart_process, start_process, stop_process};
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     http::{header, StatusCode, Request},
-    response::{IntoResponse},
+    middleware::{self, Next},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Redirect, Response,
+    },
     routing::{get, post},
-    Json, Router, body::Body,
+    Extension, Json, Router, body::Body,
 };
 use chrono::Local;
 use include_dir::{include_dir, Dir};
 use mime_guess::from_path;
+use once_cell::sync::Lazy;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::str::FromStr;
 
 use tokio::net::TcpListener;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_stream::StreamExt;
 
 // 嵌入静态文件
 static STATIC_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/static");
@@ -28,8 +40,19 @@ struct AppState {
 
 // 启动服务器
 pub async fn start_server(host: &str,port: u16) -> anyhow::Result<()> {
+    // 配置文件无法解析时不启动，避免以空配置运行
+    let errors = crate::config::parse_errors();
+    if !errors.is_empty() {
+        return Err(anyhow::anyhow!("{}\nFix the config (see zapm config validate) before starting the server", errors.join("\n")));
+    }
+
     // 启动进程监控
+    Lazy::force(&crate::metrics::DAEMON_STARTED);
+    crate::events::start();
     crate::process::start_process_monitor();
+    crate::schedule::start_scheduler();
+    crate::watch::start_watcher();
+    crate::metrics::start_sampler();
 
     // 创建路由
     let app = Router::new()
@@ -39,26 +62,110 @@ pub async fn start_server(host: &str,port: u16) -> anyhow::Result<()> {
         .route("/api/processes/:name/start", post(|path, req| async { start_process_handler(path, req).await }))
         .route("/api/processes/:name/stop", post(stop_process_handler))
         .route("/api/processes/:name/restart", post(restart_process_handler))
+        .route("/api/processes/:name/scale", post(scale_process_handler))
+        .route("/api/processes/:name/reload", post(reload_process_handler))
+        .route("/api/processes/:name/metrics", get(process_metrics_handler))
+        .route("/api/processes/:name/logs", get(process_logs_handler))
+        .route("/api/stats", get(stats_handler))
+        .route("/api/events", get(events_handler))
+        .route("/api/events/stream", get(events_stream_handler))
         .route("/api/processes/:name", post(update_process_handler))
         .route("/api/processes/:name", axum::routing::delete(delete_process_handler))
-        .route("/static/*path", get(static_handler));
+        .route("/metrics", get(metrics_handler))
+        .route("/static/*path", get(static_handler))
+        .route("/login", get(login_page_handler))
+        .route("/api/login", post(login_handler))
+        .route("/api/logout", post(logout_handler))
+        .route("/api/session", get(session_handler))
+        .route("/api/hosts", get(hosts_handler))
+        .route("/api/hosts/:host/*path", axum::routing::any(host_proxy_handler))
+        .route("/api/config/reload", post(reload_config_handler))
+        .layer(middleware::from_fn(actor_middleware))
+        .layer(middleware::from_fn(auth::middleware));
+
+    let listener = bind(host, port).await?;
+    println!("Server listening on http://{}", format!("{}:{}", host, port));
+    if hub::enabled() {
+        println!("Hub mode enabled with {} hosts", crate::config::SERVER_CONF.read().unwrap().hub.as_ref().map(|hub| hub.hosts.len()).unwrap_or(0));
+    }
+
+    // 收到 SIGHUP 时重新加载配置
+    #[cfg(unix)]
+    {
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                events::REQUEST_ACTOR.scope("sighup".to_string(), reload_on_signal()).await;
+            }
+        });
+    }
+
+    // 启动服务器，监听地址改变时在新地址上启动，原来的连接处理完后关闭
+    let (rebind_tx, mut rebind_rx) = tokio::sync::mpsc::unbounded_channel();
+    *REBIND.lock().unwrap() = Some(rebind_tx);
+    let mut listener = listener.into_std()?;
+    loop {
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = axum::Server::from_tcp(listener)?
+            .serve(app.clone().into_make_service())
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+        let mut serving = tokio::spawn(server);
+        tokio::select! {
+            result = &mut serving => {
+                result??;
+                return Ok(());
+            }
+            next = rebind_rx.recv() => match next {
+                Some(next) => {
+                    listener = next;
+                    let _ = shutdown_tx.send(());
+                }
+                None => return Ok(()),
+            },
+        }
+    }
+}
 
-    // 绑定地址
-    // let addr = SocketAddr::from(([0, 0, 0, 0], port));
+// 新的监听地址，由重新加载配置时发送
+static REBIND: Lazy<std::sync::Mutex<Option<tokio::sync::mpsc::UnboundedSender<std::net::TcpListener>>>> =
+    Lazy::new(|| std::sync::Mutex::new(None));
+
+// 绑定监听地址
+pub async fn bind(host: &str, port: u16) -> anyhow::Result<TcpListener> {
     let mut host_port_string = format!("{}:{}", host, port);
     if host == "localhost" {
         host_port_string = format!("127.0.0.1:{}",port);
     }
-  
-    let listener = TcpListener::bind(host_port_string).await?;
-    println!("Server listening on http://{}", format!("{}:{}", host, port));
+    TcpListener::bind(&host_port_string)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", host_port_string, e))
+}
 
-    // 启动服务器
-    axum::Server::from_tcp(listener.into_std()?)?
-        .serve(app.into_make_service())
-        .await?;
+// 换用新的监听地址
+pub fn rebind(listener: TcpListener) -> anyhow::Result<()> {
+    let listener = listener.into_std()?;
+    match REBIND.lock().unwrap().as_ref() {
+        Some(sender) => sender.send(listener).map_err(|_| anyhow::anyhow!("The server is shutting down")),
+        None => Err(anyhow::anyhow!("The server is not running")),
+    }
+}
+
+async fn reload_on_signal() {
+    println!("Received SIGHUP, reloading configuration");
+    match crate::reload::reload().await {
+        Ok(report) => report.print(),
+        Err(e) => eprintln!("Failed to reload configuration: {:#}", e),
+    }
+}
 
-    Ok(())
+// 重新加载配置处理器
+async fn reload_config_handler() -> Response {
+    match crate::reload::reload().await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": format!("{:#}", e) }))).into_response(),
+    }
 }
 
 // 首页处理器
@@ -66,6 +173,95 @@ async fn index_handler() -> impl IntoResponse {
     static_handler(Path("index.html".to_string())).await
 }
 
+// 登录页面处理器，未启用认证时直接进入首页
+async fn login_page_handler() -> Response {
+    if !auth::enabled() {
+        return Redirect::to("/").into_response();
+    }
+    static_handler(Path("login.html".to_string())).await.into_response()
+}
+
+// 登录请求，用户名和密码或 API 令牌二选一
+#[derive(Deserialize)]
+struct LoginRequest {
+    name: Option<String>,
+    password: Option<String>,
+    token: Option<String>,
+}
+
+// 登录处理器：校验凭据并设置 HttpOnly 会话 cookie
+async fn login_handler(Json(payload): Json<LoginRequest>) -> Response {
+    if !auth::enabled() {
+        return (StatusCode::BAD_REQUEST, "Authentication is not enabled").into_response();
+    }
+    let user = auth::authenticate(payload.name.as_deref(), payload.password.as_deref(), payload.token.as_deref());
+    let user = match user {
+        Some(user) => user,
+        None => {
+            // 减缓暴力尝试
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            return (StatusCode::UNAUTHORIZED, "Invalid credentials").into_response();
+        }
+    };
+    let ttl = match auth::session_ttl() {
+        Ok(ttl) => ttl,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let (id, identity) = auth::create_session(&user, ttl);
+    (
+        StatusCode::OK,
+        [(header::SET_COOKIE, auth::session_cookie(&id, ttl))],
+        Json(session_json(Some(&identity))),
+    )
+        .into_response()
+}
+
+// 退出登录处理器
+async fn logout_handler(request: Request<Body>) -> impl IntoResponse {
+    if let Some(id) = auth::session_id(&request) {
+        auth::remove_session(&id);
+    }
+    (StatusCode::OK, [(header::SET_COOKIE, auth::clear_cookie())])
+}
+
+// 当前会话处理器，Web 界面据此隐藏用户无权执行的操作
+async fn session_handler(identity: Option<Extension<Identity>>) -> impl IntoResponse {
+    Json(session_json(identity.as_ref().map(|Extension(identity)| identity)))
+}
+
+// 未启用认证时拥有全部权限
+fn session_json(identity: Option<&Identity>) -> serde_json::Value {
+    match identity {
+        Some(identity) => serde_json::json!({
+            "auth": true,
+            "name": identity.name,
+            "role": identity.role.as_str(),
+            "csrf_token": identity.csrf_token,
+            "hub": hub::enabled(),
+        }),
+        None => serde_json::json!({ "auth": false, "role": Role::Admin.as_str(), "hub": hub::enabled() }),
+    }
+}
+
+// agent 列表处理器，返回每个 agent 的状态和进程
+async fn hosts_handler(identity: Option<Extension<Identity>>) -> Response {
+    if !hub::enabled() {
+        return (StatusCode::NOT_FOUND, "Hub mode is not enabled").into_response();
+    }
+    process_json(&hub::status().await, identity)
+}
+
+// 把 /api/hosts/:host/* 转发到 agent 的 /api/*
+async fn host_proxy_handler(Path((host, path)): Path<(String, String)>, request: Request<Body>) -> Response {
+    if !hub::enabled() {
+        return (StatusCode::NOT_FOUND, "Hub mode is not enabled").into_response();
+    }
+    // 使用未解码的路径，进程名中编码过的字符原样转发
+    let raw = request.uri().path().strip_prefix("/api/hosts/").and_then(|rest| rest.split_once('/'));
+    let path = raw.map(|(_, path)| path.to_string()).unwrap_or(path);
+    hub::proxy(&host, &path, request).await
+}
+
 // 静态文件处理器
 async fn static_handler(Path(path): Path<String>) -> impl IntoResponse {
     let path = path.trim_start_matches('/');
@@ -98,16 +294,195 @@ async fn static_handler(Path(path): Path<String>) -> impl IntoResponse {
     )
 }
 
+// Prometheus 指标处理器
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::metrics::render(),
+    )
+}
+
+// 当前资源使用情况处理器
+async fn stats_handler() -> impl IntoResponse {
+    Json(crate::metrics::current_usage())
+}
+
+// 日志查询参数
+#[derive(Deserialize)]
+struct LogsQuery {
+    lines: Option<usize>,
+}
+
+// 进程日志处理器
+async fn process_logs_handler(
+    Path(name): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> impl IntoResponse {
+    match crate::process::tail_log(&name, query.lines.unwrap_or(100)) {
+        Ok(log) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; charset=utf-8")], log).into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    }
+}
+
+// 历史资源查询参数
+#[derive(Deserialize)]
+struct MetricsQuery {
+    since: Option<String>,
+    step: Option<String>,
+}
+
+// 进程历史资源使用情况处理器
+async fn process_metrics_handler(
+    Path(name): Path<String>,
+    Query(query): Query<MetricsQuery>,
+) -> impl IntoResponse {
+    if get_process(&name).is_none() {
+        return (StatusCode::NOT_FOUND, "Process not found").into_response();
+    }
+
+    let since = match query.since.as_deref().map(crate::utils::parse_since).transpose() {
+        Ok(since) => since,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let step = match query.step.as_deref().map(crate::utils::parse_seconds).transpose() {
+        Ok(step) => step,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    Json(serde_json::json!({
+        "name": name,
+        "interval": crate::metrics::sample_interval().as_secs(),
+        "samples": crate::metrics::query(&name, since, step),
+    }))
+    .into_response()
+}
+
+// 事件日志查询参数
+#[derive(Deserialize)]
+struct EventsQuery {
+    process: Option<String>,
+    since: Option<String>,
+    limit: Option<usize>,
+}
+
+// 事件日志处理器
+async fn events_handler(Query(query): Query<EventsQuery>) -> impl IntoResponse {
+    let since = match query.since.as_deref().map(crate::utils::parse_since).transpose() {
+        Ok(since) => since,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match events::query(query.process.as_deref(), since, query.limit) {
+        Ok(events) => Json(events).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response(),
+    }
+}
+
+// 事件流查询参数
+#[derive(Deserialize)]
+struct StreamQuery {
+    process: Option<String>,
+}
+
+// 事件流处理器：以 SSE 推送之后发生的事件，事件名为事件类型，数据为事件 JSON
+async fn events_stream_handler(Query(query): Query<StreamQuery>) -> impl IntoResponse {
+    let stream = BroadcastStream::new(events::subscribe()).filter_map(move |event| {
+        // 落后太多丢弃的事件由客户端重新获取列表补齐
+        let event = match event {
+            Ok(event) => event,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                return Some(Ok(SseEvent::default().event("lagged").data(skipped.to_string())));
+            }
+        };
+        if !query.process.as_deref().map(|name| event.is_about(name)).unwrap_or(true) {
+            return None;
+        }
+        SseEvent::default()
+            .event(event.event.as_str())
+            .json_data(&event)
+            .ok()
+            .map(Ok::<_, Infallible>)
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// 记录请求的操作者：启用认证时为认证的用户，未认证的请求记为 api。
+// X-Zapm-Actor 由命令行（cli:<用户名>）或 hub（hub 上的操作者）发送：通过 API 令牌认证时命令行记为 cli:<令牌的用户>，
+// hub 转发的请求记为 hub:<令牌的用户>/<操作者>，不会丢掉令牌的用户；未启用认证时只采用命令行的操作者
+async fn actor_middleware<B>(request: Request<B>, next: Next<B>) -> Response {
+    let identity = request.extensions().get::<Identity>();
+    let forwarded = request
+        .headers()
+        .get(events::ACTOR_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty());
+    let actor = match (identity, forwarded) {
+        (Some(identity), Some(actor)) if identity.csrf_token.is_none() => match actor.strip_prefix("cli:") {
+            Some(_) => format!("cli:{}", identity.name),
+            None => format!("hub:{}/{}", identity.name, actor),
+        },
+        (Some(identity), _) => identity.actor(),
+        (None, Some(actor)) if !auth::enabled() && actor.starts_with("cli:") => actor.to_string(),
+        (None, _) => "api".to_string(),
+    };
+    events::REQUEST_ACTOR.scope(actor, next.run(request)).await
+}
+
+// 返回进程信息，Operator 以下的角色看不到环境变量的值
+fn process_json<T: serde::Serialize>(value: &T, identity: Option<Extension<Identity>>) -> Response {
+    let mut body = match serde_json::to_value(value) {
+        Ok(body) => body,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    if !auth::can_view_env(identity.as_deref()) {
+        auth::mask_env(&mut body);
+    }
+    Json(body).into_response()
+}
+
+// 进程查询参数，view 时返回包括资源使用情况的运行时信息，detailed 时还包括 cgroup、资源限制和集群实例
+#[derive(Deserialize)]
+struct ProcessQuery {
+    #[serde(default)]
+    view: bool,
+    #[serde(default)]
+    detailed: bool,
+}
+
 // 进程列表请求处理器
-async fn list_processes_handler() -> impl IntoResponse {
-    let processes = PROCESSES.read().unwrap();
-    Json(processes.clone())
+async fn list_processes_handler(Query(query): Query<ProcessQuery>, identity: Option<Extension<Identity>>) -> Response {
+    if query.view {
+        // 采样 CPU 使用率需要等待，不阻塞运行时
+        return match tokio::task::spawn_blocking(crate::process::views).await {
+            Ok(views) => process_json(&views, identity),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        };
+    }
+    let processes = PROCESSES.read().unwrap().clone();
+    // 集群显示由实例汇总的状态
+    let processes: HashMap<String, ProcessConfig> = processes
+        .into_iter()
+        .map(|(name, config)| (name, cluster::with_summary(config)))
+        .collect();
+    process_json(&processes, identity)
 }
 
 // 获取单个进程处理器
-async fn get_process_handler(Path(name): Path<String>) -> impl IntoResponse {
+async fn get_process_handler(
+    Path(name): Path<String>,
+    Query(query): Query<ProcessQuery>,
+    identity: Option<Extension<Identity>>,
+) -> impl IntoResponse {
+    if query.view || query.detailed {
+        let view = tokio::task::spawn_blocking(move || crate::process::view(&name, query.detailed)).await;
+        return match view {
+            Ok(Ok(view)) => process_json(&view, identity),
+            Ok(Err(_)) => (StatusCode::NOT_FOUND, "Process not found").into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        };
+    }
     if let Some(process) = get_process(&name) {
-        return (StatusCode::OK, Json(process)).into_response();
+        return process_json(&cluster::with_summary(process), identity);
     }
     
     (StatusCode::NOT_FOUND, "Process not found").into_response()
@@ -142,6 +517,7 @@ async fn start_process_handler(
             "Unsupported Media Type: Content-Type must be application/json".as_bytes().to_vec(),
         );
     }
+    let identity = request.extensions().get::<Identity>().cloned();
 
     // 解析JSON请求体
     let payload = match hyper::body::to_bytes(request.into_body()).await {
@@ -175,6 +551,22 @@ async fn start_process_handler(
             );
         }
     };
+    // 覆盖启动参数会修改进程配置，Operator 只能按现有配置启动
+    let overrides = !payload.command.is_empty()
+        || payload.working_dir.is_some()
+        || payload.env.is_some()
+        || payload.auto_restart.is_some();
+    if let Some(identity) = &identity {
+        let required = auth::start_role(overrides);
+        if identity.role < required {
+            return (
+                StatusCode::FORBIDDEN,
+                [(header::CONTENT_TYPE, "text/plain".to_string())],
+                auth::forbidden_message(identity, required).as_bytes().to_vec(),
+            );
+        }
+    }
+
     // 将环境变量转换为Vec<String>格式
     let mut env_vars = payload.env.map(|map| {
         map.iter()
@@ -187,8 +579,8 @@ async fn start_process_handler(
     
     // 如果配置存在，使用配置中的命令和工作目录
     let (command, working_dir, env_to_use) = if let Some(cfg) = &config {
-        // 如果进程已经在运行，直接返回成功
-        if cfg.status == ProcessStatus::Running {
+        // 如果进程已经在运行，直接返回成功；集群需要检查每个实例
+        if cfg.status == ProcessStatus::Running && !cluster::is_parent(cfg) {
             return (
                 StatusCode::OK,
                 [(header::CONTENT_TYPE, "text/plain".to_string())],
@@ -221,12 +613,15 @@ async fn start_process_handler(
         (&payload.command, payload.working_dir.as_deref(), env_vars.as_ref())
     };
     
-    match start_process(
-        &name,
-        command,
-        working_dir,
-        env_to_use,
-    ) {
+    // 启动进程会执行钩子，在阻塞线程中执行
+    let (process, command, working_dir, env_to_use) =
+        (name.clone(), command.clone(), working_dir.map(str::to_string), env_to_use.cloned());
+    let result = events::spawn_blocking(move || {
+        start_process(&process, &command, working_dir.as_deref(), env_to_use.as_ref())
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.into()));
+    match result {
         Ok(_) => {
             // 更新自动重启设置
             if let Some(auto_restart) = payload.auto_restart {
@@ -243,27 +638,77 @@ async fn start_process_handler(
             )
         }
         Err(e) => {
+            emit_error(&name, "start", &e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 [(header::CONTENT_TYPE, "text/plain".to_string())],
-                e.to_string().as_bytes().to_vec(),
+                format!("{:#}", e).as_bytes().to_vec(),
             )
         }
     }
 }
 
+// 操作失败时发送 errored 事件
+fn emit_error(name: &str, action: &str, error: &anyhow::Error) {
+    events::emit(Event::new(EventKind::Errored, name).message(format!("{} failed: {:#}", action, error)));
+}
+
 // 停止进程处理器
 async fn stop_process_handler(Path(name): Path<String>) -> impl IntoResponse {
-    match stop_process(&name) {
-        Ok(_) => StatusCode::OK.into_response(),
+    // 停止进程最多等待 kill_timeout，在阻塞线程中执行
+    let process = name.clone();
+    match events::spawn_blocking(move || stop_process(&process)).await {
+        Ok(Ok(_)) => StatusCode::OK.into_response(),
+        Ok(Err(e)) => {
+            emit_error(&name, "stop", &e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
 
 // 重启进程处理器
 async fn restart_process_handler(Path(name): Path<String>) -> impl IntoResponse {
-    match restart_process(&name) {
+    let process = name.clone();
+    match events::spawn_blocking(move || restart_process(&process)).await {
+        Ok(Ok(_)) => StatusCode::OK.into_response(),
+        Ok(Err(e)) => {
+            emit_error(&name, "restart", &e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+// 滚动重启处理器
+async fn reload_process_handler(Path(name): Path<String>) -> impl IntoResponse {
+    match cluster::reload(&name).await {
         Ok(_) => StatusCode::OK.into_response(),
+        Err(e) => {
+            emit_error(&name, "reload", &e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response()
+        }
+    }
+}
+
+// 调整实例数请求
+#[derive(Deserialize)]
+struct ScaleProcessRequest {
+    instances: InstancesValue,
+}
+
+// 调整集群实例数处理器
+async fn scale_process_handler(
+    Path(name): Path<String>,
+    Json(payload): Json<ScaleProcessRequest>,
+) -> impl IntoResponse {
+    let process = name.clone();
+    match events::spawn_blocking(move || cluster::scale(&process, payload.instances)).await {
+        Ok(Ok(_)) => StatusCode::OK.into_response(),
+        Ok(Err(e)) => {
+            emit_error(&name, "scale", &e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response()
+        }
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
@@ -274,6 +719,62 @@ struct UpdateProcessRequest {
     command: String,
     working_dir: Option<String>,
     env: Option<HashMap<String, String>>,
+    #[serde(default)]
+    env_file: Option<Vec<String>>,
+    #[serde(default)]
+    inherit_env: Option<bool>,
+    #[serde(default)]
+    env_allowlist: Option<Vec<String>>,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    groups: Option<Vec<String>>,
+    #[serde(default)]
+    rlimits: Option<HashMap<String, RlimitValue>>,
+    #[serde(default)]
+    memory_max: Option<String>,
+    #[serde(default)]
+    cpu_weight: Option<u32>,
+    #[serde(default)]
+    cpu_max: Option<String>,
+    #[serde(default)]
+    pids_max: Option<u64>,
+    #[serde(default)]
+    max_memory: Option<String>,
+    #[serde(default)]
+    max_cpu_percent: Option<f32>,
+    #[serde(default)]
+    max_cpu_duration: Option<String>,
+    #[serde(default)]
+    kill_timeout: Option<String>,
+    #[serde(default)]
+    log_file: Option<String>,
+    #[serde(default)]
+    instances: Option<InstancesValue>,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    health_check: Option<HealthCheck>,
+    #[serde(default)]
+    listen: Option<Vec<String>>,
+    #[serde(rename = "type", default)]
+    process_type: ProcessType,
+    #[serde(default)]
+    schedule: Option<String>,
+    #[serde(default)]
+    allow_overlap: Option<bool>,
+    #[serde(default)]
+    cron_restart: Option<String>,
+    #[serde(default)]
+    hooks: Option<Hooks>,
+    #[serde(default)]
+    watch: Option<WatchValue>,
+    #[serde(default)]
+    ignore: Option<Vec<String>>,
+    #[serde(default)]
+    watch_delay: Option<String>,
     auto_restart: bool,
 }
 
@@ -285,7 +786,8 @@ async fn update_process_handler(
     let now = Local::now().to_rfc3339();
     
     // 检查进程是否存在
-    let mut config = if let Some(existing) = get_process(&name) {
+    let previous = get_process(&name);
+    let mut config = if let Some(existing) = previous.clone() {
         existing
     } else {
         // 创建新的进程配置
@@ -300,6 +802,7 @@ async fn update_process_handler(
             pid: None,
             created_at: now.clone(),
             updated_at: now.clone(),
+            ..Default::default()
         }
     };
     
@@ -307,23 +810,69 @@ async fn update_process_handler(
     config.command = payload.command;
     config.working_dir = payload.working_dir;
     config.env = payload.env;
+    config.env_file = payload.env_file;
+    config.inherit_env = payload.inherit_env;
+    config.env_allowlist = payload.env_allowlist;
+    config.user = payload.user;
+    config.group = payload.group;
+    config.groups = payload.groups;
+    config.rlimits = payload.rlimits;
+    config.memory_max = payload.memory_max;
+    config.cpu_weight = payload.cpu_weight;
+    config.cpu_max = payload.cpu_max;
+    config.pids_max = payload.pids_max;
+    config.max_memory = payload.max_memory;
+    config.max_cpu_percent = payload.max_cpu_percent;
+    config.max_cpu_duration = payload.max_cpu_duration;
+    config.kill_timeout = payload.kill_timeout;
+    config.log_file = payload.log_file;
+    config.instances = payload.instances;
+    config.port = payload.port;
+    config.health_check = payload.health_check;
+    config.listen = payload.listen;
+    config.process_type = payload.process_type;
+    config.schedule = payload.schedule;
+    config.allow_overlap = payload.allow_overlap;
+    config.cron_restart = payload.cron_restart;
+    config.hooks = payload.hooks;
+    config.watch = payload.watch;
+    config.ignore = payload.ignore;
+    config.watch_delay = payload.watch_delay;
     config.auto_restart = payload.auto_restart;
     config.updated_at = now;
+    // 校验失败时返回第一个错误和所有出错的字段
+    let errors = crate::process::validate_fields(&config);
+    if let Some((_, first)) = errors.first() {
+        let fields: HashMap<&str, &str> = errors.iter().map(|(field, error)| (*field, error.as_str())).collect();
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": first, "fields": fields })),
+        )
+            .into_response();
+    }
     
-    match update_process(config) {
-        Ok(_) => StatusCode::OK.into_response(),
+    match update_process(config.clone()) {
+        Ok(_) => {
+            events::record_update(previous.as_ref(), &config);
+            StatusCode::OK.into_response()
+        }
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
 
+// 删除进程请求参数，force 时不停止进程
+#[derive(Deserialize)]
+struct DeleteQuery {
+    #[serde(default)]
+    force: bool,
+}
+
 // 删除进程处理器
-async fn delete_process_handler(Path(name): Path<String>) -> impl IntoResponse {
-    // 先停止进程
-    let _ = stop_process(&name);
-    
-    // 删除进程配置
-    match crate::config::remove_process(&name) {
-        Ok(_) => StatusCode::OK.into_response(),
+async fn delete_process_handler(Path(name): Path<String>, Query(query): Query<DeleteQuery>) -> impl IntoResponse {
+    // 停止进程后删除配置，集群会同时删除所有实例
+    match events::spawn_blocking(move || crate::process::remove_process(&name, query.force)).await {
+        Ok(Ok(_)) => StatusCode::OK.into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
\ No newline at end of file
@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::net::TcpListener;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::ProcessConfig;
+
+// 守护进程持有的监听套接字，按分组（集群为集群名）保存，进程重启时保持打开
+static LISTENERS: Lazy<Mutex<HashMap<String, Vec<Listener>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// systemd 约定的第一个文件描述符
+const LISTEN_FDS_START: RawFd = 3;
+const LISTEN_PID_PREFIX: &[u8] = b"LISTEN_PID=";
+
+enum Socket {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+struct Listener {
+    address: String,
+    socket: Socket,
+}
+
+impl Listener {
+    fn fd(&self) -> RawFd {
+        match &self.socket {
+            Socket::Tcp(listener) => listener.as_raw_fd(),
+            Socket::Unix(listener) => listener.as_raw_fd(),
+        }
+    }
+}
+
+// 校验监听地址：tcp://host:port 或 unix:///path
+pub fn validate(address: &str) -> Result<()> {
+    match address.split_once("://") {
+        Some(("tcp", addr)) if !addr.is_empty() => Ok(()),
+        Some(("unix", path)) if path.starts_with('/') => Ok(()),
+        _ => Err(anyhow::anyhow!(
+            "Invalid listen address '{}', expected tcp://host:port or unix:///path",
+            address
+        )),
+    }
+}
+
+fn bind(address: &str) -> Result<Listener> {
+    validate(address)?;
+    let socket = if let Some(addr) = address.strip_prefix("tcp://") {
+        Socket::Tcp(TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", address))?)
+    } else {
+        let path = address.trim_start_matches("unix://");
+        // 删除上次遗留的套接字文件
+        if fs::symlink_metadata(path).map(|m| m.file_type().is_socket()).unwrap_or(false) {
+            let _ = fs::remove_file(path);
+        }
+        Socket::Unix(UnixListener::bind(path).with_context(|| format!("Failed to listen on {}", address))?)
+    };
+    Ok(Listener { address: address.to_string(), socket })
+}
+
+// 打开或复用监听套接字，地址变化时重新绑定
+fn open(group: &str, addresses: &[String]) -> Result<Vec<RawFd>> {
+    let mut listeners = LISTENERS.lock().unwrap();
+    let unchanged = listeners
+        .get(group)
+        .map(|current| current.iter().map(|l| &l.address).eq(addresses.iter()))
+        .unwrap_or(false);
+    if !unchanged {
+        listeners.remove(group);
+        let opened = addresses.iter().map(|a| bind(a)).collect::<Result<Vec<_>>>()?;
+        listeners.insert(group.to_string(), opened);
+    }
+    Ok(listeners[group].iter().map(Listener::fd).collect())
+}
+
+// 关闭分组的监听套接字，在删除进程时调用
+pub fn close(group: &str) {
+    LISTENERS.lock().unwrap().remove(group);
+}
+
+// 按 PATH 查找程序，execve 不会搜索 PATH
+fn resolve_program(program: &str, env: &HashMap<String, String>) -> Result<PathBuf> {
+    if program.contains('/') {
+        return Ok(PathBuf::from(program));
+    }
+    let path = env.get("PATH").map(String::as_str).unwrap_or("/usr/local/bin:/usr/bin:/bin");
+    path.split(':')
+        .map(|dir| Path::new(dir).join(program))
+        .find(|candidate| {
+            fs::metadata(candidate)
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        })
+        .ok_or_else(|| anyhow::anyhow!("Program {} not found in PATH", program))
+}
+
+// 在子进程中传递套接字并执行程序
+// LISTEN_PID 必须是子进程自己的 PID，所以由我们在 fork 之后自行 execve，参数和环境变量在 fork 之前准备好
+pub struct Exec {
+    fds: Vec<RawFd>,
+    // 目标位置 3、4、5 ... 上原有文件描述符的副本，execve 失败时恢复，预先分配好避免在 fork 之后分配内存
+    saved: Vec<RawFd>,
+    program: CString,
+    _args: Vec<CString>,
+    _env: Vec<CString>,
+    listen_pid: Vec<u8>,
+    argv: Vec<*const libc::c_char>,
+    envp: Vec<*const libc::c_char>,
+}
+
+// 指针只指向 Exec 自己持有的数据
+unsafe impl Send for Exec {}
+unsafe impl Sync for Exec {}
+
+// 为配置了 listen 的进程准备套接字，未配置时关闭之前打开的套接字
+pub fn prepare(
+    config: &ProcessConfig,
+    program: &str,
+    args: &[&str],
+    env: &HashMap<String, String>,
+) -> Result<Option<Exec>> {
+    let addresses = match &config.listen {
+        Some(addresses) if !addresses.is_empty() => addresses,
+        _ => {
+            close(config.group_name());
+            return Ok(None);
+        }
+    };
+    let fds = open(config.group_name(), addresses)?;
+
+    let program_path = CString::new(resolve_program(program, env)?.to_string_lossy().into_owned())?;
+    let args = std::iter::once(program)
+        .chain(args.iter().copied())
+        .map(CString::new)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let names = vec![config.group_name(); fds.len()].join(":");
+    let mut vars: Vec<CString> = env
+        .iter()
+        .filter(|(key, _)| !key.starts_with("LISTEN_"))
+        .map(|(key, value)| CString::new(format!("{}={}", key, value)))
+        .collect::<Result<Vec<_>, _>>()?;
+    vars.push(CString::new(format!("LISTEN_FDS={}", fds.len()))?);
+    vars.push(CString::new(format!("LISTEN_FDNAMES={}", names))?);
+
+    // 预留足够写下 PID 和结尾 NUL 的空间
+    let mut listen_pid = LISTEN_PID_PREFIX.to_vec();
+    listen_pid.resize(LISTEN_PID_PREFIX.len() + 21, 0);
+
+    let argv = args.iter().map(|a| a.as_ptr()).chain(std::iter::once(std::ptr::null())).collect();
+    let envp = vars
+        .iter()
+        .map(|v| v.as_ptr())
+        .chain([listen_pid.as_ptr() as *const libc::c_char, std::ptr::null()])
+        .collect();
+
+    Ok(Some(Exec {
+        saved: vec![-1; fds.len()],
+        fds,
+        program: program_path,
+        _args: args,
+        _env: vars,
+        listen_pid,
+        argv,
+        envp,
+    }))
+}
+
+impl Exec {
+    // 在 fork 之后调用，只能使用 async-signal-safe 的函数，成功时不会返回
+    pub fn exec(&mut self) -> std::io::Result<()> {
+        unsafe {
+            // 先复制到不会冲突的位置，再依次 dup2 到 3、4、5 ...，dup2 会清除 close-on-exec
+            let base = LISTEN_FDS_START + self.fds.len() as RawFd;
+            for fd in self.fds.iter_mut() {
+                let copy = libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, base);
+                if copy < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                *fd = copy;
+            }
+            // 目标位置上可能已经有打开的文件描述符，如 std 用来报告 exec 失败的管道（close-on-exec），
+            // 同样复制一份到不会冲突的位置，execve 失败时恢复原位，成功时副本随 exec 关闭
+            for (index, saved) in self.saved.iter_mut().enumerate() {
+                let target = LISTEN_FDS_START + index as RawFd;
+                if libc::fcntl(target, libc::F_GETFD) >= 0 {
+                    *saved = libc::fcntl(target, libc::F_DUPFD_CLOEXEC, base);
+                    if *saved < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+            }
+            for (index, fd) in self.fds.iter().enumerate() {
+                if libc::dup2(*fd, LISTEN_FDS_START + index as RawFd) < 0 {
+                    return self.restore(std::io::Error::last_os_error());
+                }
+            }
+
+            let mut pid = libc::getpid() as u32;
+            let mut digits = [0u8; 20];
+            let mut len = 0;
+            loop {
+                digits[len] = b'0' + (pid % 10) as u8;
+                pid /= 10;
+                len += 1;
+                if pid == 0 {
+                    break;
+                }
+            }
+            let start = LISTEN_PID_PREFIX.len();
+            for i in 0..len {
+                self.listen_pid[start + i] = digits[len - 1 - i];
+            }
+            self.listen_pid[start + len] = 0;
+
+            libc::execve(self.program.as_ptr(), self.argv.as_ptr(), self.envp.as_ptr());
+        }
+        self.restore(std::io::Error::last_os_error())
+    }
+
+    // 把目标位置恢复为原来的文件描述符，使 std 能通过它的管道报告错误
+    fn restore(&self, error: std::io::Error) -> std::io::Result<()> {
+        for (index, saved) in self.saved.iter().enumerate() {
+            if *saved >= 0 {
+                unsafe {
+                    libc::dup2(*saved, LISTEN_FDS_START + index as RawFd);
+                }
+            }
+        }
+        Err(error)
+    }
+}
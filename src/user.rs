@@ -0,0 +1,254 @@
+use anyhow::Result;
+
+use crate::config::ProcessConfig;
+
+// 子进程运行身份
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub user: Option<String>,
+    pub home: Option<String>,
+    #[cfg(unix)]
+    pub uid: libc::uid_t,
+    #[cfg(unix)]
+    pub gid: libc::gid_t,
+    #[cfg(unix)]
+    pub groups: Vec<libc::gid_t>,
+    // 目标身份与守护进程当前身份不同，需要在 exec 前切换
+    #[cfg(unix)]
+    pub switch: bool,
+}
+
+// 根据配置解析运行身份并检查是否有权限切换，未配置 user/group 时返回 None
+#[cfg(unix)]
+pub fn resolve(config: &ProcessConfig) -> Result<Option<Credentials>> {
+    let mut creds = match lookup(config)? {
+        Some(creds) => creds,
+        None => return Ok(None),
+    };
+
+    let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+    let current = current_groups()?;
+    let same_groups = |groups: &[libc::gid_t]| {
+        let mut groups = groups.to_vec();
+        groups.sort_unstable();
+        groups.dedup();
+        groups == current
+    };
+    if creds.uid == euid && creds.gid == egid {
+        // 以当前身份运行时，未显式配置 groups 则沿用当前的附加组
+        if config.groups.is_none() || same_groups(&creds.groups) {
+            creds.groups = current.clone();
+            creds.switch = false;
+            return Ok(Some(creds));
+        }
+    }
+
+    // 切换身份需要 root 权限
+    if euid != 0 {
+        return Err(anyhow::anyhow!(
+            "Cannot run process {} as {}: the zapm daemon must run as root to switch user or group",
+            config.name,
+            describe(config)
+        ));
+    }
+    Ok(Some(creds))
+}
+
+// 只查找配置的用户和组，不检查权限，用于校验配置文件
+#[cfg(unix)]
+pub fn lookup(config: &ProcessConfig) -> Result<Option<Credentials>> {
+    if config.user.is_none() && config.group.is_none() && config.groups.is_none() {
+        return Ok(None);
+    }
+
+    let (uid, mut gid, user, home) = match &config.user {
+        Some(name) => {
+            let passwd = lookup_user(name)?
+                .ok_or_else(|| anyhow::anyhow!("User {} does not exist", name))?;
+            (passwd.uid, passwd.gid, Some(passwd.name), Some(passwd.home))
+        }
+        None => unsafe { (libc::geteuid(), libc::getegid(), None, None) },
+    };
+
+    if let Some(name) = &config.group {
+        gid = lookup_group(name)?.ok_or_else(|| anyhow::anyhow!("Group {} does not exist", name))?;
+    }
+
+    let groups = match &config.groups {
+        Some(names) => {
+            let mut groups = vec![gid];
+            for name in names {
+                let id = lookup_group(name)?
+                    .ok_or_else(|| anyhow::anyhow!("Group {} does not exist", name))?;
+                if !groups.contains(&id) {
+                    groups.push(id);
+                }
+            }
+            groups
+        }
+        // 与 login 的行为一致，默认使用该用户在 /etc/group 中的附加组
+        None => match &user {
+            Some(name) => user_groups(name, gid)?,
+            None => vec![gid],
+        },
+    };
+
+    Ok(Some(Credentials { user, home, uid, gid, groups, switch: true }))
+}
+
+// 守护进程当前的附加组，已排序去重
+#[cfg(unix)]
+fn current_groups() -> Result<Vec<libc::gid_t>> {
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if count < 0 {
+        return Err(anyhow::anyhow!("Failed to get supplementary groups: {}", std::io::Error::last_os_error()));
+    }
+    let mut groups = vec![0 as libc::gid_t; count as usize];
+    let count = unsafe { libc::getgroups(count, groups.as_mut_ptr()) };
+    if count < 0 {
+        return Err(anyhow::anyhow!("Failed to get supplementary groups: {}", std::io::Error::last_os_error()));
+    }
+    groups.truncate(count as usize);
+    groups.push(unsafe { libc::getegid() });
+    groups.sort_unstable();
+    groups.dedup();
+    Ok(groups)
+}
+
+#[cfg(not(unix))]
+pub fn lookup(config: &ProcessConfig) -> Result<Option<Credentials>> {
+    resolve(config)
+}
+
+#[cfg(not(unix))]
+pub fn resolve(config: &ProcessConfig) -> Result<Option<Credentials>> {
+    if config.user.is_none() && config.group.is_none() && config.groups.is_none() {
+        return Ok(None);
+    }
+    Err(anyhow::anyhow!(
+        "Cannot run process {} as {}: user and group switching is only supported on Unix",
+        config.name,
+        describe(config)
+    ))
+}
+
+fn describe(config: &ProcessConfig) -> String {
+    match (&config.user, &config.group) {
+        (Some(user), Some(group)) => format!("{}:{}", user, group),
+        (Some(user), None) => user.clone(),
+        (None, Some(group)) => format!(":{}", group),
+        (None, None) => "the requested groups".to_string(),
+    }
+}
+
+// 在 fork 之后、exec 之前切换身份，只能调用 async-signal-safe 的函数
+#[cfg(unix)]
+pub fn apply(creds: &Credentials) -> std::io::Result<()> {
+    // 身份与当前相同时不调用 setgroups 等需要 root 权限的函数
+    if !creds.switch {
+        return Ok(());
+    }
+    unsafe {
+        if libc::setgroups(creds.groups.len() as _, creds.groups.as_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::setgid(creds.gid) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::setuid(creds.uid) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+struct Passwd {
+    name: String,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    home: String,
+}
+
+// 按用户名或数字 UID 查找用户
+#[cfg(unix)]
+fn lookup_user(name: &str) -> Result<Option<Passwd>> {
+    use std::ffi::{CStr, CString};
+
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 16384];
+
+    let rc = match name.parse::<libc::uid_t>() {
+        Ok(uid) => unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) },
+        Err(_) => {
+            let c_name = CString::new(name)?;
+            unsafe { libc::getpwnam_r(c_name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) }
+        }
+    };
+    if rc != 0 {
+        return Err(anyhow::anyhow!("Failed to look up user {}: {}", name, std::io::Error::from_raw_os_error(rc)));
+    }
+    if result.is_null() {
+        return Ok(None);
+    }
+
+    unsafe {
+        Ok(Some(Passwd {
+            name: CStr::from_ptr(pwd.pw_name).to_string_lossy().into_owned(),
+            uid: pwd.pw_uid,
+            gid: pwd.pw_gid,
+            home: CStr::from_ptr(pwd.pw_dir).to_string_lossy().into_owned(),
+        }))
+    }
+}
+
+// 按组名或数字 GID 查找组
+#[cfg(unix)]
+fn lookup_group(name: &str) -> Result<Option<libc::gid_t>> {
+    use std::ffi::CString;
+
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 16384];
+
+    let rc = match name.parse::<libc::gid_t>() {
+        Ok(gid) => unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) },
+        Err(_) => {
+            let c_name = CString::new(name)?;
+            unsafe { libc::getgrnam_r(c_name.as_ptr(), &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) }
+        }
+    };
+    if rc != 0 {
+        return Err(anyhow::anyhow!("Failed to look up group {}: {}", name, std::io::Error::from_raw_os_error(rc)));
+    }
+    if result.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(grp.gr_gid))
+}
+
+// 获取用户所属的所有组
+#[cfg(unix)]
+fn user_groups(name: &str, gid: libc::gid_t) -> Result<Vec<libc::gid_t>> {
+    use std::ffi::CString;
+
+    let c_name = CString::new(name)?;
+    let mut count: libc::c_int = 64;
+    loop {
+        let mut groups = vec![0 as libc::gid_t; count as usize];
+        let previous = count;
+        #[cfg(target_os = "macos")]
+        let rc = unsafe {
+            libc::getgrouplist(c_name.as_ptr(), gid as libc::c_int, groups.as_mut_ptr() as *mut libc::c_int, &mut count)
+        };
+        #[cfg(not(target_os = "macos"))]
+        let rc = unsafe { libc::getgrouplist(c_name.as_ptr(), gid, groups.as_mut_ptr(), &mut count) };
+        if rc >= 0 {
+            groups.truncate(count as usize);
+            return Ok(groups);
+        }
+        // 缓冲区不够，按返回的数量重试
+        count = if count > previous { count } else { previous * 2 };
+    }
+}
@@ -1,9 +1,16 @@
 use std::path::Path;
 use std::{env, fs};
 use anyhow::Result;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use reqwest;
 
 use crate::config;
+use crate::context;
+use crate::events;
+use crate::hub;
+use crate::output::{self, OutputFormat};
+use crate::process::ProcessView;
+use crate::reload;
 
 
 // 确保目录存在
@@ -33,6 +40,34 @@ pub fn format_memory_size(size_in_kb: u64) -> String {
     }
 }
 
+// 解析内存大小，如 512M、1G、1024K，不带单位时为字节
+pub fn parse_memory_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        "T" | "TB" | "TIB" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(anyhow::anyhow!("Invalid memory size '{}'", size)),
+    };
+    let too_large = || anyhow::anyhow!("Memory size '{}' is too large", size);
+    // 整数按整数计算，避免大数值经过浮点数后丢失精度
+    if let Ok(number) = number.parse::<u64>() {
+        return number.checked_mul(multiplier).ok_or_else(too_large);
+    }
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid memory size '{}'", size))?;
+    let bytes = number * multiplier as f64;
+    if bytes >= u64::MAX as f64 {
+        return Err(too_large());
+    }
+    Ok(bytes as u64)
+}
+
 // 格式化运行时间
 pub fn format_uptime(seconds: u64) -> String {
     let days = seconds / (24 * 3600);
@@ -51,33 +86,300 @@ pub fn format_uptime(seconds: u64) -> String {
     }
 }
 
-// 通过 Web API 启动服务
-pub async fn start_process_via_api(name : &str) -> Result<()> {
+// 解析起始时间：RFC 3339 时间、Unix 时间戳，或 1h、30m 这样的相对时长
+pub fn parse_since(since: &str) -> Result<i64> {
+    let since = since.trim();
+    if let Ok(timestamp) = since.parse::<i64>() {
+        return Ok(timestamp);
+    }
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(since) {
+        return Ok(time.timestamp());
+    }
+    let duration = humantime::parse_duration(since)
+        .map_err(|_| anyhow::anyhow!("Invalid time '{}', expected RFC 3339, a Unix timestamp or a duration like 1h", since))?;
+    Ok(chrono::Utc::now().timestamp() - duration.as_secs() as i64)
+}
+
+// 解析时间间隔：秒数或 5m 这样的时长
+pub fn parse_seconds(value: &str) -> Result<u64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(seconds);
+    }
+    humantime::parse_duration(value)
+        .map(|d| d.as_secs())
+        .map_err(|_| anyhow::anyhow!("Invalid duration '{}'", value))
+}
+
+// 访问 Web API 的客户端，请求头中带上命令行的操作者和当前上下文的 API 令牌；
+// 启用认证时守护进程把操作者记为令牌对应的用户
+pub fn api_client_builder() -> reqwest::ClientBuilder {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Ok(actor) = reqwest::header::HeaderValue::from_str(&events::cli_actor()) {
+        headers.insert(events::ACTOR_HEADER, actor);
+    }
+    if let Some(token) = context::token() {
+        if let Ok(mut value) = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token)) {
+            value.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+    }
+    reqwest::Client::builder().default_headers(headers)
+}
+
+pub fn api_client() -> reqwest::Client {
+    api_client_builder().build().unwrap_or_default()
+}
+
+// URL 路径段中需要编码的字符，只保留 RFC 3986 中的非保留字符
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+// 对进程名等 URL 路径段进行百分号编码
+pub fn path_segment(value: &str) -> String {
+    utf8_percent_encode(value, PATH_SEGMENT).to_string()
+}
+
+// 通过 Web API 查询进程的历史资源使用情况
+pub async fn show_metrics_via_api(
+    name: &str,
+    since: Option<&str>,
+    step: Option<&str>,
+    output: OutputFormat,
+) -> Result<()> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/processes/{}/metrics", api_base_url, path_segment(name));
+    let mut query = Vec::new();
+    if let Some(since) = since {
+        query.push(("since", since));
+    }
+    if let Some(step) = step {
+        query.push(("step", step));
+    }
+    let response = api_client().get(&url).query(&query).send().await?;
+    if response.status() != 200 {
+        return Err(anyhow::anyhow!(response.text().await?));
+    }
+
+    let body: serde_json::Value = response.json().await?;
+    if !output.is_human() {
+        return output::print(output, &body);
+    }
+    let samples = body["samples"].as_array().cloned().unwrap_or_default();
+    if samples.is_empty() {
+        println!("No metrics recorded for process {}", name);
+        return Ok(());
+    }
+
+    println!("{:<20} {:>8} {:>12} {:>12} {:>6} {:>8}", "TIME", "CPU", "MEMORY", "VIRTUAL", "FDS", "THREADS");
+    for sample in samples {
+        let optional = |key: &str| sample[key].as_u64().map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<20} {:>7.1}% {:>12} {:>12} {:>6} {:>8}",
+            format_timestamp(sample["timestamp"].as_u64().unwrap_or(0)),
+            sample["cpu_percent"].as_f64().unwrap_or(0.0),
+            format_memory_size(sample["memory_bytes"].as_u64().unwrap_or(0) / 1024),
+            format_memory_size(sample["virtual_memory_bytes"].as_u64().unwrap_or(0) / 1024),
+            optional("open_fds"),
+            optional("threads"),
+        );
+    }
+    Ok(())
+}
+
+// 通过 Web API 查看事件日志
+pub async fn show_events_via_api(
+    name: Option<&str>,
+    since: Option<&str>,
+    limit: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/events", api_base_url);
+    let mut query = vec![("limit", limit.to_string())];
+    if let Some(name) = name {
+        query.push(("process", name.to_string()));
+    }
+    if let Some(since) = since {
+        query.push(("since", since.to_string()));
+    }
+    let response = api_client().get(&url).query(&query).send().await?;
+    if response.status() != 200 {
+        return Err(anyhow::anyhow!(response.text().await?));
+    }
+
+    let events: Vec<events::Event> = response.json().await?;
+    if !output.is_human() {
+        return output::print(output, &events);
+    }
+    if events.is_empty() {
+        println!("No events recorded");
+        return Ok(());
+    }
+
+    println!("{:<20} {:<10} {:<20} {:<12} MESSAGE", "TIME", "EVENT", "PROCESS", "ACTOR");
+    for event in events {
+        let time = chrono::DateTime::parse_from_rfc3339(&event.time)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or(event.time);
+        let mut message = event.message.unwrap_or_default();
+        if let Some(code) = event.exit_code {
+            message = format!("exit code {} {}", code, message).trim_end().to_string();
+        }
+        println!(
+            "{:<20} {:<10} {:<20} {:<12} {}",
+            time,
+            event.event.as_str(),
+            event.process,
+            event.actor.as_deref().unwrap_or("-"),
+            message
+        );
+    }
+    Ok(())
+}
+
+// 通过 hub 的 Web API 查看各个守护进程的状态和进程
+pub async fn show_hosts_via_api(output: OutputFormat) -> Result<()> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/hosts", api_base_url);
+    let response = api_client().get(&url).send().await?;
+    if response.status() != 200 {
+        return Err(anyhow::anyhow!(response.text().await?));
+    }
+
+    let hosts: Vec<hub::HostStatus> = response.json().await?;
+    if !output.is_human() {
+        return output::print(output, &hosts);
+    }
+    if hosts.is_empty() {
+        println!("No hosts configured");
+        return Ok(());
+    }
+
+    println!("{:<20} {:<6} {:<10} {:<9} URL", "HOST", "STATUS", "PROCESSES", "LATENCY");
+    for host in &hosts {
+        let running = host.processes.iter().filter(|p| p.config.status == config::ProcessStatus::Running).count();
+        println!(
+            "{:<20} {:<6} {:<10} {:<9} {}",
+            host.name,
+            if host.up { "up" } else { "down" },
+            format!("{}/{}", running, host.processes.len()),
+            host.latency_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".to_string()),
+            host.url
+        );
+        if let Some(error) = &host.error {
+            println!("  {}", error);
+        }
+    }
+
+    println!();
+    println!("{:<20} {:<20} {:<12} {:<10} {:<8} MEMORY", "HOST", "NAME", "STATUS", "PID", "CPU");
+    for host in &hosts {
+        for process in &host.processes {
+            let config = &process.config;
+            println!(
+                "{:<20} {:<20} {:<12} {:<10} {:<8} {}",
+                host.name,
+                config.name,
+                format!("{:?}", config.status),
+                config.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string()),
+                process.usage.map(|u| format!("{:.1}%", u.cpu_percent)).unwrap_or_else(|| "-".to_string()),
+                process.usage.map(|u| format_memory_size(u.memory_bytes / 1024)).unwrap_or_else(|| "-".to_string())
+            );
+        }
+    }
+    Ok(())
+}
+
+// 通过 Web API 查看进程日志
+pub async fn show_logs_via_api(name: &str, lines: usize) -> Result<()> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/processes/{}/logs", api_base_url, path_segment(name));
+    let response = api_client().get(&url).query(&[("lines", lines)]).send().await?;
+    if response.status() != 200 {
+        return Err(anyhow::anyhow!(response.text().await?));
+    }
+    print!("{}", response.text().await?);
+    Ok(())
+}
+
+// 通过 Web API 启动服务，返回守护进程的响应
+pub async fn start_process_via_api(name : &str) -> Result<String> {
     let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
-    let url = format!("{}/api/processes/{}/start",api_base_url, name);
-    let response = reqwest::Client::new()
+    let url = format!("{}/api/processes/{}/start", api_base_url, path_segment(name));
+    let response = api_client()
         .post(&url)
         .json(&serde_json::json!({}))
         .send()
         .await?;   
     if response.status() == 200 {
-        println!("Process {} started , {}", name,response.text().await?);
-        Ok(())
+        Ok(response.text().await?)
     } else {
         Err(anyhow::anyhow!(response.text().await?))
     }
 
 }
 
+// 通过 Web API 滚动重启进程
+pub async fn reload_process_via_api(name: &str) -> Result<()> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/processes/{}/reload", api_base_url, path_segment(name));
+    let response = api_client().post(&url).send().await?;
+    if response.status() != 200 {
+        return Err(anyhow::anyhow!(response.text().await?));
+    }
+    Ok(())
+}
+
+// 通过 Web API 调整集群的实例数
+pub async fn scale_process_via_api(name: &str, instances: &config::InstancesValue) -> Result<()> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/processes/{}/scale", api_base_url, path_segment(name));
+    let response = api_client()
+        .post(&url)
+        .json(&serde_json::json!({ "instances": instances }))
+        .send()
+        .await?;
+    if response.status() != 200 {
+        return Err(anyhow::anyhow!(response.text().await?));
+    }
+    Ok(())
+}
+
+// 通过 Web API 获取进程配置及其在服务器上的运行时信息
+pub async fn get_process_via_api(name: &str, detailed: bool) -> Result<ProcessView> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/processes/{}", api_base_url, path_segment(name));
+    let mut query = vec![("view", "true")];
+    if detailed {
+        query.push(("detailed", "true"));
+    }
+    let response = api_client().get(&url).query(&query).send().await?;
+    if response.status() != 200 {
+        return Err(anyhow::anyhow!(response.text().await?));
+    }
+    Ok(response.json().await?)
+}
+
+// 通过 Web API 获取所有进程及其运行时信息
+pub async fn list_processes_via_api() -> Result<Vec<ProcessView>> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/processes", api_base_url);
+    let response = api_client().get(&url).query(&[("view", "true")]).send().await?;
+    if response.status() != 200 {
+        return Err(anyhow::anyhow!(response.text().await?));
+    }
+    let views: std::collections::HashMap<String, ProcessView> = response.json().await?;
+    Ok(views.into_values().collect())
+}
+
 pub async fn stop_process_via_api(name: &str) -> Result<()> {
     let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
-    let url = format!("{}/api/processes/{}/stop",api_base_url, name);
-    let response = reqwest::Client::new()
+    let url = format!("{}/api/processes/{}/stop", api_base_url, path_segment(name));
+    let response = api_client()
         .post(&url)
         .send()
         .await?;
     if response.status() == 200 {
-        println!("Process {} stopped", name);
         Ok(())
     } else {
         Err(anyhow::anyhow!(response.text().await?))
@@ -87,61 +389,72 @@ pub async fn stop_process_via_api(name: &str) -> Result<()> {
 
 pub async fn restart_process_via_api(name: &str) -> Result<()> {
     let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
-    let url = format!("{}/api/processes/{}/restart",api_base_url, name);
-    let response = reqwest::Client::new()
+    let url = format!("{}/api/processes/{}/restart", api_base_url, path_segment(name));
+    let response = api_client()
         .post(&url)
         .json(&serde_json::json!({}))
         .send()
         .await?;
     if response.status() == 200 {
-        println!("Process {} restarted", name);
         Ok(())
     } else {
         Err(anyhow::anyhow!(response.text().await?))
     }
 }
 
-pub async fn delete_process_via_api(name: &str) -> Result<()> {
+pub async fn delete_process_via_api(name: &str, force: bool) -> Result<()> {
     let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
-    let url = format!("{}/api/processes/{}", api_base_url,name);
-    let response = reqwest::Client::new()
+    let url = format!("{}/api/processes/{}", api_base_url, path_segment(name));
+    let response = api_client()
         .delete(&url)
-        .json(&serde_json::json!({}))
+        .query(&[("force", force)])
         .send()
         .await?;
     if response.status() == 200 {
-        println!("Process {} removed", name);
         Ok(())
     } else {
         Err(anyhow::anyhow!(response.text().await?))
     }
 }   
 
-pub async fn update_process_via_api(name: &str, command: &str, dir: &str, env: &[(String, String)]) -> Result<()> {
+// 通过 Web API 添加或更新进程配置，校验失败时返回服务器给出的第一个错误
+pub async fn update_process_via_api(config: &config::ProcessConfig) -> Result<()> {
     let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
-    let url = format!("{}/api/processes/{}", api_base_url, name);
-    let response = reqwest::Client::new()
-        .put(&url)
-        .json(&serde_json::json!({
-            "command": command,
-            "dir": dir,
-            "env": env
-        }))
-        .send()
-        .await?;
+    let url = format!("{}/api/processes/{}", api_base_url, path_segment(&config.name));
+    let response = api_client().post(&url).json(config).send().await?;
     if response.status() == 200 {
-        println!("Process {} updated", name);
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!(response.text().await?))
+        return Ok(());
     }
-}   
+    let text = response.text().await?;
+    let message = serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .and_then(|body| body["error"].as_str().map(str::to_string))
+        .unwrap_or(text);
+    Err(anyhow::anyhow!(message))
+}
+
+
+// 通过 Web API 让守护进程重新加载配置，配置有问题时返回服务器给出的错误
+pub async fn reload_config_via_api() -> Result<reload::ReloadReport> {
+    let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
+    let url = format!("{}/api/config/reload", api_base_url);
+    let response = api_client().post(&url).send().await?;
+    if response.status() == 200 {
+        return Ok(response.json().await?);
+    }
+    let text = response.text().await?;
+    let message = serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .and_then(|body| body["error"].as_str().map(str::to_string))
+        .unwrap_or(text);
+    Err(anyhow::anyhow!(message))
+}
 
 
 pub async fn add_process_via_api(name: &str, command: &str, dir: &str, env: &[(String, String)]) -> Result<()> {
     let api_base_url = config::SERVER_CONF.read().unwrap().api_base_url.to_string();
     let url = format!("{}/api/processes", api_base_url);
-    let response = reqwest::Client::new()
+    let response = api_client()
         .post(url)
         .json(&serde_json::json!({
             "name": name,
@@ -234,4 +547,56 @@ pub fn uninstall_service() -> Result<()> {
     } else {
         Err(anyhow::anyhow!("无法在您的操作系统中删除服务，请使用 systemd 或其他方法手动卸载"))
     }
-}   
\ No newline at end of file
+}   
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_memory_sizes() {
+        let cases = [
+            ("0", 0),
+            ("1024", 1024),
+            ("512B", 512),
+            ("1K", 1024),
+            ("1kb", 1024),
+            ("2KiB", 2048),
+            ("512M", 512 * 1024 * 1024),
+            ("512 MB", 512 * 1024 * 1024),
+            (" 1G ", 1024 * 1024 * 1024),
+            ("1.5G", 1536 * 1024 * 1024),
+            ("0.5K", 512),
+            ("2T", 2 * 1024 * 1024 * 1024 * 1024),
+            ("16777215T", 16777215 * 1024 * 1024 * 1024 * 1024),
+            ("18446744073709551615", u64::MAX),
+            ("9007199254740993", 9007199254740993),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_memory_size(input).unwrap(), expected, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_memory_sizes() {
+        for input in [
+            "", "M", "abc", "-1M", "1X", "1MBs", "1.2.3M", "16777216T", "18446744073709551616", "99999999999999999999.5T",
+        ] {
+            assert!(parse_memory_size(input).is_err(), "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn encodes_path_segments() {
+        let cases = [
+            ("api", "api"),
+            ("api-1.v2_x~", "api-1.v2_x~"),
+            ("a/b", "a%2Fb"),
+            ("a b", "a%20b"),
+            ("a?b#c%d", "a%3Fb%23c%25d"),
+            ("进程", "%E8%BF%9B%E7%A8%8B"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(path_segment(input), expected, "input {:?}", input);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::auth;
+use crate::config::{ProcessConfig, ServerConf};
+use crate::events::EventKind;
+use crate::output::{self, OutputFormat};
+use crate::process;
+use crate::utils;
+use crate::webhooks;
+
+// 配置文件中的一个问题
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    // 出错的配置项，如 api.health_check.timeout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        if let Some(path) = &self.path {
+            write!(f, ": {}", path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+// 检查结果，收集问题时按配置项查找所在的行
+struct Checker<'a> {
+    file: String,
+    content: &'a str,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(path: &Path, content: &'a str) -> Self {
+        Checker {
+            file: path.display().to_string(),
+            content,
+            problems: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, path: String, message: impl Into<String>) {
+        self.problems.push(Problem {
+            file: self.file.clone(),
+            line: locate(self.content, &path),
+            column: None,
+            path: Some(path),
+            message: message.into(),
+        });
+    }
+
+    // 解析配置，未知的字段（通常是拼写错误）也作为问题报告
+    fn parse<T: DeserializeOwned>(&mut self) -> Option<T> {
+        let mut unknown = Vec::new();
+        let deserializer = serde_yaml::Deserializer::from_str(self.content);
+        // Option 字段在路径中显示为 ?，去掉
+        let result: Result<T, _> = serde_ignored::deserialize(deserializer, |path| unknown.push(path.to_string().replace(".?", "")));
+        match result {
+            Ok(value) => {
+                for path in unknown {
+                    self.report(path, "unknown field");
+                }
+                Some(value)
+            }
+            Err(e) => {
+                let location = e.location();
+                // 位置单独输出，去掉消息末尾的 "at line X column Y"
+                let message = e.to_string();
+                let message = match message.rsplit_once(" at line ") {
+                    Some((message, _)) if location.is_some() => message.to_string(),
+                    _ => message,
+                };
+                self.problems.push(Problem {
+                    file: self.file.clone(),
+                    line: location.as_ref().map(|l| l.line()),
+                    column: location.as_ref().map(|l| l.column()),
+                    path: None,
+                    message,
+                });
+                None
+            }
+        }
+    }
+}
+
+// 按缩进查找配置项所在的行，JSON 风格等找不到时返回 None
+fn locate(content: &str, path: &str) -> Option<usize> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut start = 0;
+    let mut parent_indent: Option<usize> = None;
+    let mut found = None;
+    for segment in path.split('.') {
+        let index = segment.parse::<usize>().ok();
+        let mut items = 0;
+        let mut hit = None;
+        for (i, line) in lines.iter().enumerate().skip(start) {
+            let trimmed = line.trim_start();
+            let key = trimmed.trim_start_matches("- ");
+            if key.is_empty() || key.starts_with('#') {
+                continue;
+            }
+            let indent = line.len() - key.len();
+            if parent_indent.map(|parent| indent <= parent).unwrap_or(false) {
+                break;
+            }
+            let matches = match index {
+                // 序列的第 index 项，从该项的 "- " 所在行继续查找
+                Some(index) if trimmed.starts_with("- ") => {
+                    items += 1;
+                    items == index + 1
+                }
+                Some(_) => false,
+                None => [format!("{}:", segment), format!("'{}':", segment), format!("\"{}\":", segment)]
+                    .iter()
+                    .any(|prefix| key.starts_with(prefix.as_str())),
+            };
+            if matches {
+                hit = Some((i, indent));
+                break;
+            }
+        }
+        let (i, indent) = hit?;
+        found = Some(i + 1);
+        match index {
+            Some(_) => {
+                start = i;
+                parent_indent = Some(lines[i].len() - lines[i].trim_start().len());
+            }
+            None => {
+                start = i + 1;
+                parent_indent = Some(indent);
+            }
+        }
+    }
+    found
+}
+
+fn check_duration(checker: &mut Checker, path: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        if humantime::parse_duration(value).is_err() {
+            checker.report(path.to_string(), format!("Invalid duration '{}'", value));
+        }
+    }
+}
+
+fn check_url(checker: &mut Checker, path: String, url: &str) {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        checker.report(path, format!("Invalid URL '{}', expected http:// or https://", url));
+    }
+}
+
+// 检查 processes.yaml
+fn check_processes(checker: &mut Checker) {
+    if checker.content.trim().is_empty() {
+        return;
+    }
+    let processes: HashMap<String, ProcessConfig> = match checker.parse() {
+        Some(processes) => processes,
+        None => return,
+    };
+    let mut names: Vec<&String> = processes.keys().collect();
+    names.sort();
+    for name in names {
+        let config = &processes[name];
+        if &config.name != name {
+            checker.report(format!("{}.name", name), format!("The name '{}' does not match its key '{}'", config.name, name));
+        }
+        for (field, message) in process::validate_fields(config) {
+            checker.report(format!("{}.{}", name, field), message);
+        }
+    }
+}
+
+// 检查 zapm.yaml
+fn check_server(checker: &mut Checker) {
+    let conf: ServerConf = match checker.parse() {
+        Some(conf) => conf,
+        None => return,
+    };
+    check_duration(checker, "metrics_interval", &conf.metrics_interval);
+    check_duration(checker, "metrics_retention", &conf.metrics_retention);
+    check_duration(checker, "events_retention", &conf.events_retention);
+    if let Some(size) = &conf.log_max_size {
+        if let Err(e) = utils::parse_memory_size(size) {
+            checker.report("log_max_size".to_string(), e.to_string());
+        }
+    }
+
+    for (i, webhook) in conf.webhooks.iter().enumerate() {
+        check_url(checker, format!("webhooks.{}.url", i), &webhook.url);
+        for event in webhook.events.iter().flatten() {
+            if serde_json::from_value::<EventKind>(serde_json::json!(event)).is_err() {
+                checker.report(format!("webhooks.{}.events", i), format!("Unknown event '{}'", event));
+            }
+        }
+        if webhook.retries.map(|retries| retries > webhooks::MAX_RETRIES).unwrap_or(false) {
+            checker.report(format!("webhooks.{}.retries", i), format!("At most {} retries are allowed", webhooks::MAX_RETRIES));
+        }
+    }
+
+    if let Some(auth) = &conf.auth {
+        check_duration(checker, "auth.session_ttl", &auth.session_ttl);
+        let mut seen = HashSet::new();
+        for (i, user) in auth.users.iter().enumerate() {
+            let path = format!("auth.users.{}", i);
+            if !seen.insert(user.name.as_str()) {
+                checker.report(format!("{}.name", path), format!("Duplicate user '{}'", user.name));
+            }
+            if user.password_hash.is_none() && user.token.is_none() {
+                checker.report(path.clone(), format!("User '{}' has neither a password_hash nor a token", user.name));
+            }
+            if let Some(hash) = &user.password_hash {
+                if !auth::is_valid_hash(hash) {
+                    checker.report(format!("{}.password_hash", path), "Invalid password hash, generate one with zapm hash-password");
+                }
+            }
+        }
+    }
+
+    if let Some(hub) = &conf.hub {
+        let mut seen = HashSet::new();
+        for (i, host) in hub.hosts.iter().enumerate() {
+            if !seen.insert(host.name.as_str()) {
+                checker.report(format!("hub.hosts.{}.name", i), format!("Duplicate host '{}'", host.name));
+            }
+            check_url(checker, format!("hub.hosts.{}.url", i), &host.url);
+        }
+    }
+}
+
+// 检查配置内容，问题按在文件中出现的顺序排列，找不到行号的排在最后
+fn check_content(path: &Path, content: &str) -> Vec<Problem> {
+    let mut checker = Checker::new(path, content);
+    let is_server = matches!(path.file_name().and_then(|n| n.to_str()), Some("zapm.yaml" | "zapm.yml"));
+    if is_server {
+        check_server(&mut checker);
+    } else {
+        check_processes(&mut checker);
+    }
+    let mut problems = checker.problems;
+    problems.sort_by_key(|problem| (problem.line.is_none(), problem.line, problem.column));
+    problems
+}
+
+// 检查配置文件，zapm.yaml 按服务器配置检查，其他文件按进程配置检查
+pub fn check_file(path: &Path) -> Result<Vec<Problem>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(check_content(path, &content))
+}
+
+// 检查配置文件并输出问题，有问题时返回错误
+pub fn run(files: &[&Path], output: OutputFormat) -> Result<()> {
+    let mut problems = Vec::new();
+    for file in files {
+        let found = check_file(file)?;
+        if found.is_empty() && output.is_human() {
+            println!("{}: OK", file.display());
+        }
+        problems.extend(found);
+    }
+
+    if !output.is_human() {
+        output::print(output, &problems)?;
+    } else {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+    }
+    match problems.len() {
+        0 => Ok(()),
+        1 => Err(anyhow::anyhow!("1 problem found")),
+        n => Err(anyhow::anyhow!("{} problems found", n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "\
+# comment
+api:
+  command: ./api
+  health_check:
+
+    http: http://localhost/health
+    'timeout': 5s
+worker:
+  command: ./worker
+  \"env\":
+    A: 1
+auth:
+  users:
+    - name: a
+      role: admin
+    - name: b
+      token: secret
+";
+
+    #[test]
+    fn locates_paths() {
+        let cases = [
+            ("api", Some(2)),
+            ("api.command", Some(3)),
+            ("api.health_check.http", Some(6)),
+            ("api.health_check.timeout", Some(7)),
+            ("worker.command", Some(9)),
+            ("worker.env.A", Some(11)),
+            ("auth.users.0", Some(14)),
+            ("auth.users.0.name", Some(14)),
+            ("auth.users.0.role", Some(15)),
+            ("auth.users.1.name", Some(16)),
+            ("auth.users.1.token", Some(17)),
+            ("auth.users.2", None),
+            ("api.env", None),
+            // 只在父级之下查找，不会匹配到其他进程的同名字段
+            ("api.health_check.command", None),
+            ("missing", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(locate(CONTENT, path), expected, "path {:?}", path);
+        }
+    }
+
+    #[test]
+    fn reports_problems_in_source_order() {
+        let content = "\
+zeta:
+  name: zeta
+  command: ''
+  auto_restart: false
+  status: Stopped
+  pid: null
+  created_at: ''
+  updated_at: ''
+  tpyo: 1
+alpha:
+  name: alpha
+  command: x
+  kill_timeout: soon
+  auto_restart: false
+  status: Stopped
+  pid: null
+  created_at: ''
+  updated_at: ''
+";
+        let problems = check_content(Path::new("processes.yaml"), content);
+        let found: Vec<(Option<usize>, &str)> =
+            problems.iter().map(|p| (p.line, p.path.as_deref().unwrap_or(""))).collect();
+        assert_eq!(
+            found,
+            [(Some(3), "zeta.command"), (Some(9), "zeta.tpyo"), (Some(13), "alpha.kill_timeout")]
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_column() {
+        let problems = check_content(Path::new("processes.yaml"), "api:\n  command: [1\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].line, problems[0].column), (Some(2), Some(12)));
+        assert!(!problems[0].message.contains(" at line "));
+
+        let problems = check_content(Path::new("zapm.yaml"), "host: localhost\nport: 2400\nevents_retention: forever\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].to_string(), "zapm.yaml:3: events_retention: Invalid duration 'forever'");
+    }
+
+    #[test]
+    fn rejects_too_many_webhook_retries() {
+        let content = "host: localhost\nport: 2400\nwebhooks:\n  - url: http://localhost/hook\n    retries: 10\n  - url: http://localhost/hook\n    retries: 4294967295\n";
+        let problems = check_content(Path::new("zapm.yaml"), content);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].to_string(), "zapm.yaml:7: webhooks.1.retries: At most 10 retries are allowed");
+    }
+
+    #[test]
+    fn accepts_empty_process_config() {
+        assert!(check_content(Path::new("processes.yaml"), "\n").is_empty());
+    }
+}
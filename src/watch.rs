@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use crate::config::{self, get_process, ProcessConfig, ProcessStatus, WatchValue, PROCESSES};
+use crate::events::{self, Event, EventKind as ProcessEvent};
+use crate::process;
+
+const DEFAULT_DELAY: Duration = Duration::from_secs(1);
+// 检查配置变化的间隔
+const SYNC_INTERVAL: Duration = Duration::from_secs(2);
+// 始终忽略的目录
+const DEFAULT_IGNORE: &[&str] = &["**/.git/**", "**/node_modules/**"];
+
+// 进程的监视设置，变化时重新创建监视器
+#[derive(PartialEq)]
+struct Spec {
+    roots: Vec<PathBuf>,
+    ignore: Vec<String>,
+    delay: Duration,
+    // 进程自己写入的日志文件，避免写日志触发重启
+    logs: Vec<PathBuf>,
+}
+
+struct Watch {
+    spec: Spec,
+    ignore: GlobSet,
+    // 创建失败时为 None，设置变化后再重试
+    _watcher: Option<RecommendedWatcher>,
+}
+
+impl Watch {
+    // 返回变化文件相对于监视目录的路径，忽略的文件返回 None
+    fn relative(&self, path: &Path) -> Option<PathBuf> {
+        if self.spec.logs.iter().any(|log| log == path) {
+            return None;
+        }
+        let relative = self
+            .spec
+            .roots
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        (!self.ignore.is_match(relative)).then(|| relative.to_path_buf())
+    }
+}
+
+fn spec(config: &ProcessConfig, all: &[ProcessConfig]) -> Result<Option<Spec>> {
+    let paths = match &config.watch {
+        None | Some(WatchValue::Enabled(false)) => return Ok(None),
+        Some(WatchValue::Paths(paths)) if !paths.is_empty() => paths.clone(),
+        Some(_) => vec![".".to_string()],
+    };
+    let roots = paths
+        .iter()
+        .map(|path| match config.working_dir.as_deref() {
+            _ if Path::new(path).is_absolute() => Ok(PathBuf::from(path)),
+            Some(dir) => Ok(Path::new(dir).join(path)),
+            None => Err(anyhow::anyhow!(
+                "Process {} watches relative path '{}' but has no working_dir",
+                config.name,
+                path
+            )),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let delay = match &config.watch_delay {
+        Some(delay) => humantime::parse_duration(delay).with_context(|| format!("Invalid watch_delay '{}'", delay))?,
+        None => DEFAULT_DELAY,
+    };
+    let logs = all
+        .iter()
+        .filter(|c| c.name == config.name || c.instance_of.as_deref() == Some(&config.name))
+        .map(config::log_path)
+        .collect();
+    Ok(Some(Spec {
+        roots,
+        ignore: config.ignore.clone().unwrap_or_default(),
+        delay,
+        logs,
+    }))
+}
+
+fn build_ignore(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in DEFAULT_IGNORE.iter().copied().chain(patterns.iter().map(String::as_str)) {
+        builder.add(Glob::new(pattern).with_context(|| format!("Invalid ignore pattern '{}'", pattern))?);
+    }
+    Ok(builder.build()?)
+}
+
+// 校验监视设置
+pub fn validate(config: &ProcessConfig) -> Result<()> {
+    if let Some(spec) = spec(config, &[])? {
+        build_ignore(&spec.ignore)?;
+    }
+    Ok(())
+}
+
+fn watch(name: &str, roots: &[PathBuf], tx: Sender<(String, PathBuf)>) -> Result<RecommendedWatcher> {
+    let process = name.to_string();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        if let Ok(event) = result {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+                for path in event.paths {
+                    let _ = tx.send((process.clone(), path));
+                }
+            }
+        }
+    })?;
+    for root in roots {
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", root.display()))?;
+    }
+    Ok(watcher)
+}
+
+// 根据配置创建、更新或删除监视器
+fn sync(watches: &mut HashMap<String, Watch>, tx: &Sender<(String, PathBuf)>) {
+    let configs: Vec<ProcessConfig> = PROCESSES.read().unwrap().values().cloned().collect();
+    let mut wanted = HashMap::new();
+    for config in configs.iter().filter(|c| c.instance_of.is_none()) {
+        if let Ok(Some(spec)) = spec(config, &configs) {
+            wanted.insert(config.name.clone(), spec);
+        }
+    }
+
+    watches.retain(|name, watch| wanted.get(name) == Some(&watch.spec));
+    for (name, spec) in wanted {
+        if watches.contains_key(&name) {
+            continue;
+        }
+        let result = build_ignore(&spec.ignore)
+            .and_then(|ignore| Ok((ignore, watch(&name, &spec.roots, tx.clone())?)));
+        let (ignore, watcher) = match result {
+            Ok((ignore, watcher)) => (ignore, Some(watcher)),
+            Err(e) => {
+                println!("Failed to watch files of process {}: {:#}", name, e);
+                (GlobSet::empty(), None)
+            }
+        };
+        watches.insert(name, Watch { spec, ignore, _watcher: watcher });
+    }
+}
+
+fn restart(name: &str, path: &Path) {
+    // 只重启正在运行的进程
+    if get_process(name).map(|c| c.status != ProcessStatus::Running).unwrap_or(true) {
+        return;
+    }
+    let reason = format!("watch: {} changed", path.display());
+    println!("Restarting process {}: {}", name, reason);
+    if let Err(e) = process::restart_process_with_reason(name, &reason) {
+        println!("Failed to restart process {}: {:#}", name, e);
+        events::emit(Event::new(ProcessEvent::Errored, name).message(format!("{:#}", e)));
+    }
+}
+
+// 启动文件监视线程，文件变化后等待 watch_delay 没有新的变化再重启进程
+pub fn start_watcher() {
+    std::thread::spawn(|| {
+        events::set_thread_actor("watch");
+        let (tx, rx) = mpsc::channel::<(String, PathBuf)>();
+        let mut watches: HashMap<String, Watch> = HashMap::new();
+        // 等待重启的进程：最后一次变化的时间和变化的文件
+        let mut pending: HashMap<String, (Instant, PathBuf)> = HashMap::new();
+        let mut last_sync: Option<Instant> = None;
+
+        loop {
+            if last_sync.map(|t| t.elapsed() >= SYNC_INTERVAL).unwrap_or(true) {
+                sync(&mut watches, &tx);
+                pending.retain(|name, _| watches.contains_key(name));
+                last_sync = Some(Instant::now());
+            }
+
+            match rx.recv_timeout(Duration::from_millis(200)) {
+                Ok((name, path)) => {
+                    if let Some(relative) = watches.get(&name).and_then(|watch| watch.relative(&path)) {
+                        pending.insert(name, (Instant::now(), relative));
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            let ready: Vec<String> = pending
+                .iter()
+                .filter(|(name, (changed, _))| watches.get(*name).map(|w| changed.elapsed() >= w.spec.delay).unwrap_or(true))
+                .map(|(name, _)| name.clone())
+                .collect();
+            for name in ready {
+                if let Some((_, path)) = pending.remove(&name) {
+                    restart(&name, &path);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,103 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+use crate::config::{WebhookConf, SERVER_CONF};
+use crate::events::Event;
+
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_RETRIES: u32 = 3;
+// retries 的上限，由 zapm config validate 检查
+pub const MAX_RETRIES: u32 = 10;
+// 第一次重试前等待的时间，之后每次加倍，最长 512s
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_BACKOFF_EXPONENT: u32 = 9;
+
+// 第 attempt 次重试前等待的时间
+fn backoff(attempt: u32) -> Duration {
+    RETRY_DELAY * 2u32.saturating_pow(attempt.saturating_sub(1).min(MAX_BACKOFF_EXPONENT))
+}
+
+// 请求体的 HMAC-SHA256 签名
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// 把事件发送给所有订阅了该事件的 webhook，每个 webhook 独立发送和重试
+pub fn dispatch(client: &reqwest::Client, event: &Event) {
+    let webhooks = SERVER_CONF.read().unwrap().webhooks.clone();
+    let body = match serde_json::to_string(event) {
+        Ok(body) => body,
+        Err(_) => return,
+    };
+    for webhook in webhooks {
+        let subscribed = webhook
+            .events
+            .as_ref()
+            .map(|events| events.iter().any(|e| e == event.event.as_str()))
+            .unwrap_or(true);
+        if subscribed {
+            tokio::spawn(deliver(client.clone(), webhook, event.event.as_str(), body.clone()));
+        }
+    }
+}
+
+async fn deliver(client: reqwest::Client, webhook: WebhookConf, event: &'static str, body: String) {
+    let retries = webhook.retries.unwrap_or(DEFAULT_RETRIES).min(MAX_RETRIES);
+    let mut last_error = String::new();
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff(attempt)).await;
+        }
+        let mut request = client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Zapm-Event", event)
+            .body(body.clone());
+        if let Some(secret) = &webhook.secret {
+            request = request.header("X-Zapm-Signature", format!("sha256={}", sign(secret, &body)));
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => last_error = format!("HTTP {}", response.status()),
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+    println!(
+        "Failed to deliver {} event to webhook {} after {} attempts: {}",
+        event,
+        webhook.url,
+        retries + 1,
+        last_error
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231 测试用例 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            sign("", ""),
+            "b613679a0814d9ec772f95d778c35fc5ff1697c493715653c6c712144292c5ad"
+        );
+    }
+
+    #[test]
+    fn doubles_backoff_up_to_the_limit() {
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff(4), Duration::from_secs(8));
+        assert_eq!(backoff(10), Duration::from_secs(512));
+        assert_eq!(backoff(11), Duration::from_secs(512));
+        assert_eq!(backoff(u32::MAX), Duration::from_secs(512));
+    }
+}
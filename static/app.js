@@ -4,66 +4,217 @@ async function toggleAddProcessForm() {
     document.getElementById('addProcess').classList.toggle('hidden');
 }
 
+// Signed-in user from /api/session; without authentication everyone is an admin
+let session = { auth: false, role: 'admin' };
+const ROLES = ['viewer', 'operator', 'admin'];
+
+function can(role) {
+    return ROLES.indexOf(session.role) >= ROLES.indexOf(role);
+}
+
+// fetch() that sends the CSRF token with changes and returns to the login page when the session ends
+async function api(url, options = {}) {
+    const method = (options.method || 'GET').toUpperCase();
+    if (method !== 'GET' && session.csrf_token) {
+        options.headers = { ...(options.headers || {}), 'X-CSRF-Token': session.csrf_token };
+    }
+    const response = await fetch(url, options);
+    if (response.status === 401 && session.auth) {
+        location.href = '/login';
+    }
+    return response;
+}
+
+async function loadSession() {
+    const response = await fetch('/api/session');
+    if (response.status === 401) {
+        location.href = '/login';
+        return false;
+    }
+    session = await response.json();
+    if (session.auth) {
+        document.getElementById('sessionUser').textContent = `${session.name} (${session.role})`;
+        document.getElementById('sessionBar').classList.remove('hidden');
+    }
+    // Hide actions the user's role can't perform
+    document.querySelectorAll('[data-role]').forEach(el => {
+        el.classList.toggle('hidden', !can(el.dataset.role));
+    });
+    document.getElementById('fleet').classList.toggle('hidden', !session.hub);
+    return true;
+}
+
+document.getElementById('logout').addEventListener('click', async () => {
+    await api('/api/logout', { method: 'POST' });
+    location.href = '/login';
+});
+
+// Process shown in the detail view, null while the list is shown
+let currentProcess = null;
+let currentConfig = null;
+let logTimer = null;
+
+function escapeHtml(value) {
+    return String(value)
+        .replace(/&/g, '&amp;')
+        .replace(/</g, '&lt;')
+        .replace(/>/g, '&gt;')
+        .replace(/"/g, '&quot;')
+        .replace(/'/g, '&#39;');
+}
+
+function statusClass(status) {
+    return status === 'Running' ? 'status-running' :
+        status === 'Stopped' ? 'status-stopped' :
+        status === 'Failed' ? 'status-failed' :
+        status === 'Completed' ? 'status-completed' : '';
+}
+
+function formatBytes(bytes) {
+    if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(0)} KB`;
+    if (bytes < 1024 * 1024 * 1024) return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+    return `${(bytes / 1024 / 1024 / 1024).toFixed(2)} GB`;
+}
+
+// Cluster instances are named <name>-<id>
+function belongsTo(processName, name) {
+    return processName === name ||
+        (processName.startsWith(`${name}-`) && /^\d+$/.test(processName.slice(name.length + 1)));
+}
+
+// Refresh whichever view is shown
+function refresh() {
+    if (currentProcess) {
+        loadDetail();
+    } else {
+        fetchProcesses();
+    }
+}
+
 // Fetch all processes
 async function fetchProcesses() {
     try {
-        const response = await fetch('/api/processes');
+        const response = await api('/api/processes');
         const data = await response.json();
-        
+
         const tableBody = document.querySelector('#processTable tbody');
         tableBody.innerHTML = '';
-        
+
         Object.values(data).forEach(process => {
             const row = document.createElement('tr');
-            
-            const statusClass = 
-                process.status === 'Running' ? 'status-running' : 
-                process.status === 'Stopped' ? 'status-stopped' : 
-                process.status === 'Failed' ? 'status-failed' : '';
-            
+
             row.innerHTML = `
-                <td>${process.name}</td>
-                <td>${process.command}</td>
-                <td class="${statusClass}">${process.status}</td>
+                <td><a href="#/process/${encodeURIComponent(process.name)}">${escapeHtml(process.name)}</a></td>
+                <td>${escapeHtml(process.command)}</td>
+                <td class="${statusClass(process.status)}">${process.status}</td>
                 <td>${process.pid || '-'}</td>
                 <td class="actions">
-                    <button class="btn-start" data-name="${process.name}">Start</button>
-                    <button class="btn-stop" data-name="${process.name}">Stop</button>
-                    <button class="btn-restart" data-name="${process.name}">Restart</button>
-                    <button class="btn-delete" data-name="${process.name}">Delete</button>
+                    ${can('operator') ? `
+                    <button class="btn-start" data-name="${escapeHtml(process.name)}">Start</button>
+                    <button class="btn-stop" data-name="${escapeHtml(process.name)}">Stop</button>
+                    <button class="btn-restart" data-name="${escapeHtml(process.name)}">Restart</button>` : ''}
+                    ${can('admin') ? `
+                    <button class="btn-delete" data-name="${escapeHtml(process.name)}">Delete</button>` : ''}
                 </td>
             `;
-            
+
             tableBody.appendChild(row);
         });
-        
+
         // Add event listeners to buttons
-        document.querySelectorAll('.btn-start').forEach(btn => {
+        document.querySelectorAll('#processTable .btn-start').forEach(btn => {
             btn.addEventListener('click', () => startProcess(btn.dataset.name));
         });
-        
-        document.querySelectorAll('.btn-stop').forEach(btn => {
+
+        document.querySelectorAll('#processTable .btn-stop').forEach(btn => {
             btn.addEventListener('click', () => stopProcess(btn.dataset.name));
         });
-        
-        document.querySelectorAll('.btn-restart').forEach(btn => {
+
+        document.querySelectorAll('#processTable .btn-restart').forEach(btn => {
             btn.addEventListener('click', () => restartProcess(btn.dataset.name));
         });
-        
-        document.querySelectorAll('.btn-delete').forEach(btn => {
+
+        document.querySelectorAll('#processTable .btn-delete').forEach(btn => {
             btn.addEventListener('click', () => deleteProcess(btn.dataset.name));
         });
-        
+
     } catch (error) {
         console.error('Error fetching processes:', error);
     }
 }
 
+// Hosts aggregated by a hub, with their processes
+async function fetchHosts() {
+    try {
+        const response = await api('/api/hosts');
+        if (!response.ok) return;
+        const hosts = await response.json();
+
+        renderRows('#hostTable', hosts.map(host => {
+            const running = host.processes.filter(p => p.status === 'Running').length;
+            return `<tr>
+                <td title="${escapeHtml(host.url)}">${escapeHtml(host.name)}</td>
+                <td class="${host.up ? 'status-running' : 'status-failed'}">${host.up ? 'up' : 'down'}
+                    ${host.error ? `<div class="field-error">${escapeHtml(host.error)}</div>` : ''}</td>
+                <td>${host.up ? `${running}/${host.processes.length}` : '-'}</td>
+                <td>${host.latency_ms != null ? `${host.latency_ms} ms` : '-'}</td>
+                <td>${host.last_seen ? new Date(host.last_seen).toLocaleString() : '-'}</td>
+            </tr>`;
+        }));
+
+        renderRows('#fleetTable', hosts.flatMap(host => host.processes.map(process => {
+            const button = (action, label) =>
+                `<button class="btn-${action}" data-host="${escapeHtml(host.name)}" data-name="${escapeHtml(process.name)}" data-action="${action}">${label}</button>`;
+            return `<tr>
+                <td>${escapeHtml(host.name)}</td>
+                <td>${escapeHtml(process.name)}</td>
+                <td class="${statusClass(process.status)}">${process.status}</td>
+                <td>${process.pid || '-'}</td>
+                <td>${process.usage ? `${process.usage.cpu_percent.toFixed(1)}%` : '-'}</td>
+                <td>${process.usage ? formatBytes(process.usage.memory_bytes) : '-'}</td>
+                <td class="actions">${can('operator') ?
+                    button('start', 'Start') + button('stop', 'Stop') + button('restart', 'Restart') : ''}</td>
+            </tr>`;
+        })));
+
+        document.querySelectorAll('#fleetTable button').forEach(btn => {
+            btn.addEventListener('click', () => hostAction(btn.dataset.host, btn.dataset.name, btn.dataset.action));
+        });
+    } catch (error) {
+        console.error('Error fetching hosts:', error);
+    }
+}
+
+// Start, stop or restart a process on another host through the hub
+async function hostAction(host, name, action) {
+    try {
+        const response = await api(
+            `/api/hosts/${encodeURIComponent(host)}/processes/${encodeURIComponent(name)}/${action}`,
+            { method: 'POST', headers: {'Content-Type': 'application/json'}, body: '{}' }
+        );
+        if (!response.ok) {
+            alert(await response.text());
+        }
+        fetchHosts();
+    } catch (error) {
+        console.error(`Error running ${action} on ${host}/${name}:`, error);
+    }
+}
+
+// Show the response body of a failed action in the detail view
+async function reportFailure(response) {
+    if (response.ok || !currentProcess) return;
+    const box = document.getElementById('detailError');
+    box.textContent = await response.text();
+    box.classList.remove('hidden');
+}
+
 // Start a process
 async function startProcess(name) {
     try {
-        await fetch(`/api/processes/${name}/start`, { method: 'POST', headers: {'Content-Type': 'application/json'} });
-        fetchProcesses();
+        const response = await api(`/api/processes/${encodeURIComponent(name)}/start`, { method: 'POST', headers: {'Content-Type': 'application/json'}, body: '{}' });
+        await reportFailure(response);
+        refresh();
     } catch (error) {
         console.error(`Error starting process ${name}:`, error);
     }
@@ -72,8 +223,9 @@ async function startProcess(name) {
 // Stop a process
 async function stopProcess(name) {
     try {
-        await fetch(`/api/processes/${name}/stop`, { method: 'POST' });
-        fetchProcesses();
+        const response = await api(`/api/processes/${encodeURIComponent(name)}/stop`, { method: 'POST' });
+        await reportFailure(response);
+        refresh();
     } catch (error) {
         console.error(`Error stopping process ${name}:`, error);
     }
@@ -82,8 +234,9 @@ async function stopProcess(name) {
 // Restart a process
 async function restartProcess(name) {
     try {
-        await fetch(`/api/processes/${name}/restart`, { method: 'POST' });
-        fetchProcesses();
+        const response = await api(`/api/processes/${encodeURIComponent(name)}/restart`, { method: 'POST' });
+        await reportFailure(response);
+        refresh();
     } catch (error) {
         console.error(`Error restarting process ${name}:`, error);
     }
@@ -93,35 +246,53 @@ async function restartProcess(name) {
 async function deleteProcess(name) {
     if (confirm(`Are you sure you want to delete process ${name}?`)) {
         try {
-            await fetch(`/api/processes/${name}`, { method: 'DELETE' });
-            fetchProcesses();
+            await api(`/api/processes/${encodeURIComponent(name)}`, { method: 'DELETE' });
+            if (currentProcess === name) {
+                location.hash = '';
+            } else {
+                refresh();
+            }
         } catch (error) {
             console.error(`Error deleting process ${name}:`, error);
         }
     }
 }
 
+// Parse key=value lines; values may contain '='
+function parseEnv(text) {
+    const env = {};
+    text.split('\n').forEach(line => {
+        const index = line.indexOf('=');
+        if (index > 0) {
+            env[line.slice(0, index).trim()] = line.slice(index + 1).trim();
+        }
+    });
+    return env;
+}
+
+// Validation failures come back as {"error": ..., "fields": {...}}, other failures as plain text
+async function errorMessage(response) {
+    const text = await response.text();
+    try {
+        return JSON.parse(text).error || text;
+    } catch (_) {
+        return text;
+    }
+}
+
 // Add a new process
 document.getElementById('addProcessForm').addEventListener('submit', async (e) => {
     e.preventDefault();
-    
+
     const name = document.getElementById('name').value;
     const command = document.getElementById('command').value;
     const workingDir = document.getElementById('workingDir').value;
     const envText = document.getElementById('env').value;
     const autoRestart = document.getElementById('autoRestart').checked;
-    
+
     // Parse environment variables
-    const env = {};
-    if (envText) {
-        envText.split('\n').forEach(line => {
-            const [key, value] = line.split('=');
-            if (key && value) {
-                env[key.trim()] = value.trim();
-            }
-        });
-    }
-    
+    const env = parseEnv(envText);
+
     const processData = {
         name,
         command,
@@ -129,29 +300,389 @@ document.getElementById('addProcessForm').addEventListener('submit', async (e) =
         env: Object.keys(env).length > 0 ? env : null,
         auto_restart: autoRestart
     };
-    
+
+    const errorBox = document.getElementById('addProcessError');
     try {
-        await fetch(`/api/processes/${name}`, {
+        const response = await api(`/api/processes/${encodeURIComponent(name)}`, {
             method: 'POST',
             headers: {
                 'Content-Type': 'application/json'
             },
             body: JSON.stringify(processData)
         });
-        
+        if (!response.ok) {
+            errorBox.textContent = await errorMessage(response);
+            errorBox.classList.remove('hidden');
+            return;
+        }
+        errorBox.classList.add('hidden');
+
         // Reset form
         document.getElementById('addProcessForm').reset();
-        
+
         // Refresh process list
         fetchProcesses();
-        
+
     } catch (error) {
         console.error('Error adding process:', error);
     }
 });
 
-// Initial fetch
-fetchProcesses();
+// Detail view
+
+function showDetailView(visible) {
+    document.getElementById('listView').classList.toggle('hidden', visible);
+    document.getElementById('detailView').classList.toggle('hidden', !visible);
+}
+
+function openDetail(name) {
+    currentProcess = name;
+    currentConfig = null;
+    document.getElementById('detailName').textContent = name;
+    document.getElementById('detailStatus').textContent = '';
+    document.getElementById('detailError').classList.add('hidden');
+    document.getElementById('editProcess').classList.add('hidden');
+    document.getElementById('logPane').textContent = '';
+    showDetailView(true);
+    loadDetail();
+    fetchLogs();
+    clearInterval(logTimer);
+    logTimer = setInterval(fetchLogs, 2000);
+}
+
+function closeDetail() {
+    currentProcess = null;
+    currentConfig = null;
+    clearInterval(logTimer);
+    showDetailView(false);
+    fetchProcesses();
+}
+
+function route() {
+    const match = location.hash.match(/^#\/process\/(.+)$/);
+    if (match) {
+        openDetail(decodeURIComponent(match[1]));
+    } else {
+        closeDetail();
+    }
+}
+
+function renderRows(selector, rows) {
+    const tableBody = document.querySelector(`${selector} tbody`);
+    tableBody.innerHTML = rows.join('');
+}
+
+// Env values are hidden unless "Show values" is checked
+function maskedEnv(env) {
+    const reveal = document.getElementById('revealEnv').checked;
+    const masked = {};
+    Object.keys(env || {}).sort().forEach(key => {
+        masked[key] = reveal ? env[key] : '••••••';
+    });
+    return masked;
+}
+
+function renderOverview(config) {
+    const fields = [
+        ['Status', config.status],
+        ['PID', config.pid],
+        ['Type', config.type],
+        ['Command', config.command],
+        ['Working Directory', config.working_dir],
+        ['User', config.user],
+        ['Group', config.group],
+        ['Auto Restart', config.auto_restart ? 'yes' : 'no'],
+        ['Instances', config.instances !== undefined && config.instances !== null
+            ? `${config.running_instances || 0} / ${config.instances} running` : null],
+        ['Port', config.port],
+        ['Schedule', config.schedule],
+        ['Cron Restart', config.cron_restart],
+        ['Restarts', config.restarts],
+        ['Crashes', config.crashes],
+        ['Log File', config.log_file],
+        ['Created', config.created_at],
+        ['Updated', config.updated_at],
+    ];
+    renderRows('#detailOverview', fields
+        .filter(([, value]) => value !== undefined && value !== null && value !== '')
+        .map(([label, value]) => `<tr><th>${label}</th><td>${escapeHtml(value)}</td></tr>`));
+}
+
+function renderUsage(name, stats) {
+    const rows = Object.keys(stats)
+        .filter(processName => belongsTo(processName, name))
+        .sort()
+        .map(processName => {
+            const usage = stats[processName];
+            return `<tr>
+                <td>${escapeHtml(processName)}</td>
+                <td>${usage.cpu_percent.toFixed(1)}%</td>
+                <td>${formatBytes(usage.memory_bytes)}</td>
+                <td>${formatBytes(usage.virtual_memory_bytes)}</td>
+                <td>${usage.open_fds ?? '-'}</td>
+                <td>${usage.threads ?? '-'}</td>
+            </tr>`;
+        });
+    renderRows('#detailUsage', rows.length ? rows : ['<tr><td colspan="6">Not running</td></tr>']);
+}
+
+function renderEnv(env) {
+    const reveal = document.getElementById('revealEnv').checked;
+    const rows = Object.entries(maskedEnv(env)).map(([key, value]) =>
+        `<tr><th>${escapeHtml(key)}</th><td class="${reveal ? '' : 'masked'}">${escapeHtml(value)}</td></tr>`);
+    renderRows('#detailEnv', rows.length ? rows : ['<tr><td>No environment variables set</td></tr>']);
+}
+
+function renderHistory(events) {
+    const rows = events
+        .filter(event => ['exited', 'restarted', 'unhealthy', 'errored'].includes(event.event))
+        .reverse()
+        .map(event => `<tr>
+            <td>${escapeHtml(new Date(event.time).toLocaleString())}</td>
+            <td>${event.event}</td>
+            <td>${escapeHtml(event.actor || '-')}</td>
+            <td>${event.exit_code ?? '-'}</td>
+            <td>${escapeHtml(event.message || '')}</td>
+        </tr>`);
+    renderRows('#detailHistory', rows.length ? rows : ['<tr><td colspan="5">No exits recorded</td></tr>']);
+}
+
+function renderConfig(config) {
+    const shown = { ...config, env: config.env ? maskedEnv(config.env) : config.env };
+    document.getElementById('detailConfig').textContent = JSON.stringify(shown, null, 2);
+}
+
+async function loadDetail() {
+    const name = currentProcess;
+    try {
+        const [processResponse, statsResponse, eventsResponse] = await Promise.all([
+            api(`/api/processes/${encodeURIComponent(name)}`),
+            api('/api/stats'),
+            api(`/api/events?process=${encodeURIComponent(name)}&limit=200`),
+        ]);
+        if (name !== currentProcess) return;
+        if (!processResponse.ok) {
+            const box = document.getElementById('detailError');
+            box.textContent = `Process ${name} not found`;
+            box.classList.remove('hidden');
+            return;
+        }
+        const config = await processResponse.json();
+        currentConfig = config;
+
+        const status = document.getElementById('detailStatus');
+        status.textContent = config.status;
+        status.className = statusClass(config.status);
+        renderOverview(config);
+        renderUsage(name, statsResponse.ok ? await statsResponse.json() : {});
+        renderEnv(config.env);
+        renderHistory(eventsResponse.ok ? await eventsResponse.json() : []);
+        renderConfig(config);
+    } catch (error) {
+        console.error(`Error loading process ${name}:`, error);
+    }
+}
+
+async function fetchLogs() {
+    const name = currentProcess;
+    if (!name) return;
+    try {
+        const response = await api(`/api/processes/${encodeURIComponent(name)}/logs?lines=200`);
+        if (!response.ok || name !== currentProcess) return;
+        const pane = document.getElementById('logPane');
+        const text = await response.text();
+        if (pane.textContent !== text) {
+            pane.textContent = text;
+            if (document.getElementById('followLogs').checked) {
+                pane.scrollTop = pane.scrollHeight;
+            }
+        }
+    } catch (error) {
+        console.error(`Error fetching logs of ${name}:`, error);
+    }
+}
+
+document.getElementById('revealEnv').addEventListener('change', () => {
+    if (currentConfig) {
+        renderEnv(currentConfig.env);
+        renderConfig(currentConfig);
+    }
+});
+
+document.getElementById('detailStart').addEventListener('click', () => startProcess(currentProcess));
+document.getElementById('detailStop').addEventListener('click', () => stopProcess(currentProcess));
+document.getElementById('detailRestart').addEventListener('click', () => restartProcess(currentProcess));
+document.getElementById('detailDelete').addEventListener('click', () => deleteProcess(currentProcess));
+
+// Edit form
+
+function editInput(field) {
+    return document.querySelector(`#editProcessForm [data-field="${field}"]`);
+}
+
+function clearEditErrors() {
+    document.querySelectorAll('#editProcessForm .field-error').forEach(el => { el.textContent = ''; });
+    document.getElementById('editProcessError').classList.add('hidden');
+}
+
+function openEdit() {
+    if (!currentConfig) return;
+    const config = currentConfig;
+    clearEditErrors();
+    editInput('command').value = config.command || '';
+    editInput('working_dir').value = config.working_dir || '';
+    editInput('env').value = Object.entries(config.env || {}).map(([key, value]) => `${key}=${value}`).join('\n');
+    editInput('env_file').value = (config.env_file || []).join('\n');
+    editInput('user').value = config.user || '';
+    editInput('group').value = config.group || '';
+    editInput('type').value = config.type || 'service';
+    editInput('schedule').value = config.schedule || '';
+    editInput('cron_restart').value = config.cron_restart || '';
+    editInput('instances').value = config.instances ?? '';
+    editInput('port').value = config.port ?? '';
+    editInput('kill_timeout').value = config.kill_timeout || '';
+    editInput('max_memory').value = config.max_memory || '';
+    editInput('memory_max').value = config.memory_max || '';
+    editInput('cpu_max').value = config.cpu_max || '';
+    editInput('auto_restart').checked = !!config.auto_restart;
+    document.getElementById('editProcess').classList.remove('hidden');
+}
+
+// Empty inputs clear the setting
+function optional(field) {
+    const value = editInput(field).value.trim();
+    return value === '' ? null : value;
+}
+
+function lines(field) {
+    const values = editInput(field).value.split('\n').map(line => line.trim()).filter(line => line);
+    return values.length ? values : null;
+}
+
+document.getElementById('detailEdit').addEventListener('click', openEdit);
+document.getElementById('editCancel').addEventListener('click', () => {
+    document.getElementById('editProcess').classList.add('hidden');
+});
+
+document.getElementById('editProcessForm').addEventListener('submit', async (e) => {
+    e.preventDefault();
+    const name = currentProcess;
+    const env = parseEnv(editInput('env').value);
+    const instances = optional('instances');
+    const port = optional('port');
+
+    // Settings without an input keep their current values
+    const processData = {
+        ...currentConfig,
+        command: editInput('command').value,
+        working_dir: optional('working_dir'),
+        env: Object.keys(env).length > 0 ? env : null,
+        env_file: lines('env_file'),
+        user: optional('user'),
+        group: optional('group'),
+        type: editInput('type').value,
+        schedule: optional('schedule'),
+        cron_restart: optional('cron_restart'),
+        instances: instances !== null && /^\d+$/.test(instances) ? Number(instances) : instances,
+        port: port !== null ? Number(port) : null,
+        kill_timeout: optional('kill_timeout'),
+        max_memory: optional('max_memory'),
+        memory_max: optional('memory_max'),
+        cpu_max: optional('cpu_max'),
+        auto_restart: editInput('auto_restart').checked,
+    };
+
+    clearEditErrors();
+    try {
+        const response = await api(`/api/processes/${encodeURIComponent(name)}`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(processData)
+        });
+        if (response.ok) {
+            document.getElementById('editProcess').classList.add('hidden');
+            loadDetail();
+            return;
+        }
+
+        // Show each error next to its input, the rest above the buttons
+        const text = await response.text();
+        let body = { error: text, fields: {} };
+        try {
+            body = JSON.parse(text);
+        } catch (_) {
+            // Not a validation error
+        }
+        const unplaced = [];
+        Object.entries(body.fields || {}).forEach(([field, error]) => {
+            const target = document.querySelector(`#editProcessForm [data-error-for="${field}"]`);
+            if (target) {
+                target.textContent = error;
+            } else {
+                unplaced.push(`${field}: ${error}`);
+            }
+        });
+        if (unplaced.length || Object.keys(body.fields || {}).length === 0) {
+            const box = document.getElementById('editProcessError');
+            box.textContent = unplaced.length ? unplaced.join('\n') : body.error;
+            box.classList.remove('hidden');
+        }
+    } catch (error) {
+        console.error(`Error updating process ${name}:`, error);
+    }
+});
+
+// Refresh shortly after events arrive, so a burst of events (e.g. a restart) triggers one fetch
+let refreshTimer = null;
+function scheduleRefresh(event) {
+    // The detail view only cares about its own process
+    if (currentProcess && event && event.type !== 'lagged') {
+        try {
+            if (!belongsTo(JSON.parse(event.data).process, currentProcess)) return;
+        } catch (_) {
+            // Refresh anyway
+        }
+    }
+    clearTimeout(refreshTimer);
+    refreshTimer = setTimeout(refresh, 200);
+}
 
-// Refresh every 5 seconds
-setInterval(fetchProcesses, 5000);
\ No newline at end of file
+// Subscribe to process events pushed by the daemon
+function subscribeEvents() {
+    const source = new EventSource('/api/events/stream');
+    // Fetch on every (re)connect to pick up changes missed while disconnected
+    source.addEventListener('open', () => refresh());
+    // The browser gives up reconnecting when the stream is refused, e.g. after the session expired
+    source.addEventListener('error', () => {
+        if (source.readyState === EventSource.CLOSED) {
+            loadSession();
+        }
+    });
+    ['started', 'stopped', 'exited', 'restarted', 'healthy', 'unhealthy', 'errored', 'updated', 'removed', 'lagged']
+        .forEach(type => source.addEventListener(type, scheduleRefresh));
+    return source;
+}
+
+window.addEventListener('hashchange', route);
+
+loadSession().then(signedIn => {
+    if (!signedIn) return;
+
+    // Initial fetch
+    route();
+
+    // Events from other hosts aren't streamed to the hub, so poll them
+    if (session.hub) {
+        fetchHosts();
+        setInterval(() => {
+            if (!currentProcess) fetchHosts();
+        }, 10000);
+    }
+
+    if (window.EventSource) {
+        subscribeEvents();
+    } else {
+        // Refresh every 5 seconds
+        setInterval(refresh, 5000);
+    }
+});
@@ -0,0 +1,30 @@
+// Exchange a user name and password, or an API token, for a session cookie
+document.getElementById('loginForm').addEventListener('submit', async (e) => {
+    e.preventDefault();
+
+    const token = document.getElementById('loginToken').value;
+    const credentials = token
+        ? { token }
+        : {
+            name: document.getElementById('loginName').value,
+            password: document.getElementById('loginPassword').value
+        };
+
+    const errorBox = document.getElementById('loginError');
+    try {
+        const response = await fetch('/api/login', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(credentials)
+        });
+        if (response.ok) {
+            location.href = '/';
+            return;
+        }
+        errorBox.textContent = await response.text();
+        errorBox.classList.remove('hidden');
+    } catch (error) {
+        errorBox.textContent = 'Unable to reach the server';
+        errorBox.classList.remove('hidden');
+    }
+});